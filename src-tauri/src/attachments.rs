@@ -0,0 +1,1463 @@
+use base64::Engine;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use image::{DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_opener::OpenerExt;
+use tokio::sync::Semaphore;
+
+use crate::resolve_data_dir;
+
+/// Extensions `open_path` refuses, on files and folders (macOS bundles)
+/// alike, since their default handler runs them rather than showing them
+const LAUNCHABLE_EXTENSIONS: [&str; 27] = [
+    "app", "appimage", "bat", "cmd", "com", "command", "cpl", "deb", "desktop", "exe", "hta",
+    "jar", "lnk", "msi", "pif", "pkg", "ps1", "reg", "rpm", "run", "scpt", "scr", "sh", "url",
+    "vbs", "workflow", "wsf",
+];
+
+/// Reported when neither the contents nor the extension identify a file
+const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+/// How many directory levels below a dropped folder are expanded by default
+const DEFAULT_MAX_DEPTH: usize = 8;
+
+/// How much of a text file is read for its preview by default
+const DEFAULT_PREVIEW_BYTES: usize = 1024;
+
+/// How much of a file is sampled to decide whether it's text
+const TEXT_SAMPLE_BYTES: usize = 8 * 1024;
+
+/// How many dropped files are read at once, to stay well clear of the open
+/// file limit
+const MAX_CONCURRENT_READS: usize = 16;
+
+/// Folder under the data dir where generated thumbnails are cached
+const THUMBNAIL_CACHE_DIR: &str = "thumbnails";
+
+/// Folder under the data dir that imported attachments are copied into
+const ATTACHMENTS_DIR: &str = "attachments";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachedFileInfo {
+    pub file_path: String,
+    pub file_name: String,
+    pub size_bytes: u64,
+    /// Unix epoch millis; `None` where the filesystem doesn't record them
+    pub modified_ms: Option<u64>,
+    pub created_ms: Option<u64>,
+    /// Sniffed from the contents, else guessed from the extension
+    pub mime_type: String,
+    /// Hex SHA-256 of the contents, when hashing was asked for
+    pub sha256: Option<String>,
+    /// Position within the dropped folder, for files found by expanding one
+    pub relative_path: Option<String>,
+    /// Pixel dimensions, for images whose header could be read
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Whether the contents look like text rather than binary data
+    pub is_text: bool,
+    /// Character encoding of text files, e.g. `UTF-8` or `UTF-16LE`
+    pub detected_encoding: Option<String>,
+    /// The start of the file, for text files
+    pub preview: Option<String>,
+    /// Canonical path of the file, when symlinks are followed
+    pub resolved_path: Option<String>,
+    /// Set when the file can't be attached, with the reason why
+    pub rejected: bool,
+    pub reason: Option<String>,
+    /// Path of an earlier file in the drop with the same contents
+    pub duplicate_of: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DroppedFiles {
+    pub files: Vec<AttachedFileInfo>,
+    /// Dropped paths, or entries within dropped folders, that were left out
+    pub skipped: Vec<SkippedFile>,
+    /// Set when `max_files` was hit and later files were left out
+    pub truncated: bool,
+    /// Every file found in the drop, including those past `max_files`
+    pub total_files: usize,
+}
+
+impl DroppedFiles {
+    fn skip(&mut self, path: &Path, reason: impl Into<String>) {
+        let reason = reason.into();
+        log::warn!("[Attachments] Skipping {}: {}", path.display(), reason);
+        self.skipped.push(SkippedFile {
+            path: path.to_string_lossy().to_string(),
+            reason,
+        });
+    }
+}
+
+impl AttachedFileInfo {
+    fn read(
+        path: &Path,
+        relative_path: Option<String>,
+        options: &DropOptions,
+    ) -> Result<Self, String> {
+        let resolved_path = if options.follow_symlinks {
+            Some(resolve_symlinks(path)?)
+        } else {
+            None
+        };
+        let target = resolved_path.as_deref().unwrap_or(path);
+        let metadata =
+            std::fs::metadata(target).map_err(|e| format!("Failed to read metadata: {}", e))?;
+        let mime_type = detect_mime_type(target);
+        let (width, height) = match image_dimensions(target, &mime_type) {
+            Some((w, h)) => (Some(w), Some(h)),
+            None => (None, None),
+        };
+        let encoding = read_head(target, TEXT_SAMPLE_BYTES).and_then(|s| detect_encoding(&s));
+        let preview = match encoding {
+            Some(encoding) if options.preview_bytes > 0 => {
+                text_preview(target, options.preview_bytes, encoding)
+            }
+            _ => None,
+        };
+        let reason = options.rejection(path, metadata.len());
+        Ok(Self {
+            file_path: path.to_string_lossy().to_string(),
+            file_name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+            size_bytes: metadata.len(),
+            modified_ms: epoch_millis(metadata.modified()),
+            created_ms: epoch_millis(metadata.created()),
+            mime_type,
+            sha256: None,
+            relative_path,
+            width,
+            height,
+            is_text: encoding.is_some(),
+            detected_encoding: encoding.map(|e| e.name().to_string()),
+            preview,
+            resolved_path: resolved_path.map(|p| p.to_string_lossy().to_string()),
+            rejected: reason.is_some(),
+            reason,
+            duplicate_of: None,
+        })
+    }
+}
+
+/// How dropped files are read. Every field is optional from the frontend.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DropOptions {
+    /// Hash the contents of each accepted file
+    pub hash: bool,
+    /// Hash files and point repeats at the first file with the same contents
+    pub dedupe: bool,
+    /// Expand dropped folders into the files inside them
+    pub recurse: bool,
+    /// How many levels below a dropped folder are expanded
+    pub max_depth: usize,
+    /// Cap on files read for the whole drop. Files past it are only counted.
+    pub max_files: Option<usize>,
+    /// How much of each text file is previewed; 0 turns previews off
+    pub preview_bytes: usize,
+    /// Resolve symlinks and report the target's metadata
+    pub follow_symlinks: bool,
+    /// Files larger than this are rejected
+    pub max_size_bytes: Option<u64>,
+    /// Extensions to accept, without the dot and in any case; `""` accepts
+    /// files with no extension. Empty accepts everything.
+    pub allowed_extensions: Vec<String>,
+}
+
+impl Default for DropOptions {
+    fn default() -> Self {
+        Self {
+            hash: false,
+            dedupe: false,
+            recurse: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_files: None,
+            preview_bytes: DEFAULT_PREVIEW_BYTES,
+            follow_symlinks: false,
+            max_size_bytes: None,
+            allowed_extensions: Vec::new(),
+        }
+    }
+}
+
+impl DropOptions {
+    /// Why the file at `path` of `size` bytes can't be attached, if it can't
+    fn rejection(&self, path: &Path, size: u64) -> Option<String> {
+        if !self.allowed_extensions.is_empty() {
+            let ext = path
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            let allowed = self
+                .allowed_extensions
+                .iter()
+                .any(|a| a.trim_start_matches('.').to_lowercase() == ext);
+            if !allowed {
+                return Some(if ext.is_empty() {
+                    "Files without an extension aren't allowed".to_string()
+                } else {
+                    format!(".{} files aren't allowed", ext)
+                });
+            }
+        }
+        match self.max_size_bytes {
+            Some(max) if size > max => Some(format!(
+                "File is {} bytes, over the {} byte limit",
+                size, max
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Canonical target of `path`. Broken and circular links are reported as
+/// such instead of the bare OS error.
+fn resolve_symlinks(path: &Path) -> Result<PathBuf, String> {
+    std::fs::canonicalize(path).map_err(|e| {
+        if path.is_symlink() {
+            format!("Broken or circular symlink: {}", e)
+        } else {
+            format!("Failed to resolve path: {}", e)
+        }
+    })
+}
+
+/// A file found in the drop, waiting to have its metadata read
+struct PendingFile {
+    path: PathBuf,
+    relative_path: Option<String>,
+}
+
+/// Append every file under `dir` to `pending`, in name order. Unreadable
+/// entries are skipped. Symlinked folders are only entered when following
+/// symlinks, and never when they point back up the tree.
+fn expand_dir(
+    root: &Path,
+    dir: &Path,
+    depth: usize,
+    options: &DropOptions,
+    pending: &mut Vec<PendingFile>,
+    results: &mut DroppedFiles,
+) {
+    let mut entries: Vec<_> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).collect(),
+        Err(e) => return results.skip(dir, format!("Unreadable folder: {}", e)),
+    };
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let is_link = entry.file_type().map(|t| t.is_symlink()).unwrap_or(false);
+        if path.is_dir() {
+            if is_link && !options.follow_symlinks {
+                log::debug!(
+                    "[Attachments] Not following linked folder {}",
+                    path.display()
+                );
+            } else if is_link && links_to_ancestor(&path, dir) {
+                results.skip(&path, "Circular symlink");
+            } else if depth < options.max_depth {
+                expand_dir(root, &path, depth + 1, options, pending, results);
+            } else {
+                results.skip(&path, "Too deeply nested");
+            }
+            continue;
+        }
+        if !path.is_file() {
+            if is_link && options.follow_symlinks {
+                if let Err(reason) = resolve_symlinks(&path) {
+                    results.skip(&path, reason);
+                }
+            }
+            continue;
+        }
+        let relative_path = path
+            .strip_prefix(root)
+            .map(|p| p.to_string_lossy().replace('\\', "/"))
+            .ok();
+        queue_file(
+            PendingFile {
+                path,
+                relative_path,
+            },
+            options,
+            pending,
+            results,
+        );
+    }
+}
+
+/// Queue a found file to be read, or just count it once `max_files` is hit
+fn queue_file(
+    file: PendingFile,
+    options: &DropOptions,
+    pending: &mut Vec<PendingFile>,
+    results: &mut DroppedFiles,
+) {
+    results.total_files += 1;
+    if options.max_files.is_some_and(|max| pending.len() >= max) {
+        results.truncated = true;
+    } else {
+        pending.push(file);
+    }
+}
+
+/// Find the files in a drop, expanding folders if `options.recurse` is set.
+/// Paths that can't be used are added to the skipped list of `results`.
+fn collect_pending(
+    paths: Vec<String>,
+    options: &DropOptions,
+    results: &mut DroppedFiles,
+) -> Vec<PendingFile> {
+    let mut pending = Vec::new();
+    for source_path_str in paths {
+        let source = PathBuf::from(&source_path_str);
+        if options.follow_symlinks && source.is_symlink() {
+            if let Err(reason) = resolve_symlinks(&source) {
+                results.skip(&source, reason);
+                continue;
+            }
+        }
+        if source.is_dir() && options.recurse {
+            expand_dir(&source, &source, 0, options, &mut pending, results);
+            continue;
+        }
+        if !source.is_file() {
+            continue;
+        }
+        let file = PendingFile {
+            path: source,
+            relative_path: None,
+        };
+        queue_file(file, options, &mut pending, results);
+    }
+    pending
+}
+
+/// Read the metadata of a pending file, hashing it if asked to and it wasn't
+/// rejected
+fn read_pending(file: &PendingFile, options: &DropOptions) -> Result<AttachedFileInfo, String> {
+    let mut info = AttachedFileInfo::read(&file.path, file.relative_path.clone(), options)?;
+    if (options.hash || options.dedupe) && !info.rejected {
+        info.sha256 = Some(hash_file(&file.path)?);
+    }
+    Ok(info)
+}
+
+/// Whether the folder symlink `link` points at `dir` or one of its parents
+fn links_to_ancestor(link: &Path, dir: &Path) -> bool {
+    match (std::fs::canonicalize(link), std::fs::canonicalize(dir)) {
+        (Ok(target), Ok(dir)) => dir.starts_with(target),
+        _ => true,
+    }
+}
+
+/// MIME type of the file at `path`. The contents take precedence over the
+/// extension, so a PNG saved as `photo.jpg` is still reported as a PNG.
+fn detect_mime_type(path: &Path) -> String {
+    infer::get_from_path(path)
+        .ok()
+        .flatten()
+        .map(|kind| kind.mime_type().to_string())
+        .or_else(|| mime_guess::from_path(path).first_raw().map(str::to_string))
+        .unwrap_or_else(|| DEFAULT_MIME_TYPE.to_string())
+}
+
+/// A file timestamp as Unix epoch millis, if the platform provides it
+fn epoch_millis(time: std::io::Result<SystemTime>) -> Option<u64> {
+    let since_epoch = time.ok()?.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(since_epoch.as_millis()).ok()
+}
+
+/// The first `max_bytes` of a file, without reading the rest
+fn read_head(path: &Path, max_bytes: usize) -> Option<Vec<u8>> {
+    use std::io::Read;
+
+    let mut head = Vec::with_capacity(max_bytes.min(64 * 1024));
+    std::fs::File::open(path)
+        .ok()?
+        .take(max_bytes as u64)
+        .read_to_end(&mut head)
+        .ok()?;
+    Some(head)
+}
+
+/// Encoding of a sample from the start of a file, or `None` if it looks like
+/// binary data. A byte order mark wins; otherwise NUL bytes mean binary, as
+/// editors treat them, valid UTF-8 is UTF-8, and anything else is whatever
+/// chardetng is confident about. Empty files count as UTF-8 text.
+fn detect_encoding(sample: &[u8]) -> Option<&'static Encoding> {
+    if let Some((encoding, _)) = Encoding::for_bom(sample) {
+        return Some(encoding);
+    }
+    if sample.contains(&0) {
+        return None;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => return Some(UTF_8),
+        // Only the last character was cut off by the sample size
+        Err(e) if e.error_len().is_none() => return Some(UTF_8),
+        Err(_) => {}
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(sample, false);
+    match detector.guess_assess(None, true) {
+        (encoding, true) => Some(encoding),
+        _ => None,
+    }
+}
+
+/// Up to the first `max_bytes` of a text file, decoded from `encoding`. A
+/// character cut off by the limit is dropped rather than shown as a
+/// replacement character.
+fn text_preview(path: &Path, max_bytes: usize, encoding: &'static Encoding) -> Option<String> {
+    let head = read_head(path, max_bytes)?;
+    let mut decoder = encoding.new_decoder_with_bom_removal();
+    let mut preview = String::with_capacity(decoder.max_utf8_buffer_length(head.len())?);
+    // Not passing `last`, so a partial character at the end is held back
+    let _ = decoder.decode_to_string(&head, &mut preview, false);
+    Some(preview)
+}
+
+/// Width and height of an image, read from its header without decoding the
+/// pixels. Corrupt or unsupported images give `None`.
+fn image_dimensions(path: &Path, mime_type: &str) -> Option<(u32, u32)> {
+    if !mime_type.starts_with("image/") {
+        return None;
+    }
+    image::ImageReader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+/// Hex SHA-256 of the file at `path`, read in chunks so large files don't
+/// have to fit in memory
+fn hash_file(path: &Path) -> Result<String, String> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .map_err(|e| format!("Failed to hash {}: {}", path.display(), e))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThumbnailResult {
+    pub source_path: String,
+    /// `data:` URL of the thumbnail, or `None` if the file isn't a readable image
+    pub data_url: Option<String>,
+}
+
+fn data_url(mime_type: &str, bytes: &[u8]) -> String {
+    format!(
+        "data:{};base64,{}",
+        mime_type,
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    )
+}
+
+/// Thumbnail of the image at `path` no larger than `max_edge` on its longest
+/// side, as a data URL. Images with transparency are encoded as PNG and the
+/// rest as JPEG. Results are cached under `cache_dir` by content hash and
+/// size, so dropping the same image again skips decoding.
+fn thumbnail(cache_dir: &Path, path: &Path, max_edge: u32) -> Result<Option<String>, String> {
+    if !detect_mime_type(path).starts_with("image/") {
+        return Ok(None);
+    }
+    let digest = hash_file(path)?;
+    for (ext, mime_type) in [("png", "image/png"), ("jpg", "image/jpeg")] {
+        let cached = cache_dir.join(format!("{}-{}.{}", digest, max_edge, ext));
+        if let Ok(bytes) = std::fs::read(&cached) {
+            return Ok(Some(data_url(mime_type, &bytes)));
+        }
+    }
+
+    let image = match image::ImageReader::open(path)
+        .map_err(|e| e.to_string())
+        .and_then(|r| r.with_guessed_format().map_err(|e| e.to_string()))
+        .and_then(|r| r.decode().map_err(|e| e.to_string()))
+    {
+        Ok(image) => image,
+        Err(e) => {
+            log::warn!("[Attachments] Can't decode {}: {}", path.display(), e);
+            return Ok(None);
+        }
+    };
+    let image = if image.width().max(image.height()) > max_edge {
+        image.thumbnail(max_edge, max_edge)
+    } else {
+        image
+    };
+
+    let (image, format, ext, mime_type) = if image.color().has_alpha() {
+        (image, ImageFormat::Png, "png", "image/png")
+    } else {
+        let rgb = DynamicImage::ImageRgb8(image.to_rgb8());
+        (rgb, ImageFormat::Jpeg, "jpg", "image/jpeg")
+    };
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut std::io::Cursor::new(&mut bytes), format)
+        .map_err(|e| format!("Failed to encode thumbnail of {}: {}", path.display(), e))?;
+
+    let cached = cache_dir.join(format!("{}-{}.{}", digest, max_edge, ext));
+    if let Err(e) = std::fs::create_dir_all(cache_dir).and_then(|_| std::fs::write(&cached, &bytes))
+    {
+        log::warn!(
+            "[Attachments] Failed to cache thumbnail {}: {}",
+            cached.display(),
+            e
+        );
+    }
+    Ok(Some(data_url(mime_type, &bytes)))
+}
+
+/// Generate thumbnails no larger than `max_edge` pixels for dropped images.
+/// Files that aren't images, or can't be decoded, get a `None` data URL.
+#[tauri::command]
+pub async fn generate_thumbnails(
+    app: AppHandle,
+    paths: Vec<String>,
+    max_edge: u32,
+) -> Result<Vec<ThumbnailResult>, String> {
+    if max_edge == 0 {
+        return Err("max_edge must be at least 1".to_string());
+    }
+    let cache_dir = resolve_data_dir(&app)?.join(THUMBNAIL_CACHE_DIR);
+    let tasks: Vec<_> = paths
+        .iter()
+        .map(|source| {
+            let (cache_dir, source) = (cache_dir.clone(), PathBuf::from(source));
+            tauri::async_runtime::spawn_blocking(move || thumbnail(&cache_dir, &source, max_edge))
+        })
+        .collect();
+
+    let mut results = Vec::new();
+    for (source_path, task) in paths.into_iter().zip(tasks) {
+        let data_url = match task
+            .await
+            .map_err(|e| format!("Thumbnailing failed: {}", e))?
+        {
+            Ok(data_url) => data_url,
+            Err(e) => {
+                log::warn!("[Attachments] {}", e);
+                None
+            }
+        };
+        results.push(ThumbnailResult {
+            source_path,
+            data_url,
+        });
+    }
+    Ok(results)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedFile {
+    pub original_path: String,
+    /// Where the copy now lives, if the import worked
+    pub stored_path: Option<String>,
+    /// Name the copy was stored under, which differs from the original's
+    /// when another file in the batch already had it
+    pub file_name: Option<String>,
+    pub error: Option<String>,
+    /// Original path of an earlier file in the batch with the same contents,
+    /// whose copy was reused
+    pub duplicate_of: Option<String>,
+}
+
+impl ImportedFile {
+    fn stored(original_path: String, stored: &Path, duplicate_of: Option<String>) -> Self {
+        Self {
+            original_path,
+            stored_path: Some(stored.to_string_lossy().to_string()),
+            file_name: stored.file_name().map(|n| n.to_string_lossy().to_string()),
+            error: None,
+            duplicate_of,
+        }
+    }
+}
+
+/// First name based on `name` that's free in `dir`, appending ` (1)`,
+/// ` (2)`, ... before the extension the way file managers do
+fn dedupe_name(dir: &Path, name: &str) -> String {
+    if !dir.join(name).exists() {
+        return name.to_string();
+    }
+    let path = Path::new(name);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| name.to_string());
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| format!("{} ({}){}", stem, n, extension))
+        .find(|candidate| !dir.join(candidate).exists())
+        .expect("ran out of numbered names")
+}
+
+/// Point each file at the first earlier file with the same SHA-256
+fn mark_duplicates(files: &mut [AttachedFileInfo]) {
+    let mut first_seen: HashMap<String, String> = HashMap::new();
+    for info in files {
+        if let Some(digest) = &info.sha256 {
+            match first_seen.get(digest) {
+                Some(first) => info.duplicate_of = Some(first.clone()),
+                None => {
+                    first_seen.insert(digest.clone(), info.file_path.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Copy `source` into `dir` under its own name, numbered if that's taken,
+/// streaming so large files aren't held in memory. An existing file is never
+/// overwritten, and a partial copy is removed if the copy fails.
+fn import_file(dir: &Path, source: &Path) -> Result<PathBuf, String> {
+    if !source.is_file() {
+        return Err(format!("{} is not a file", source.display()));
+    }
+    let name = source
+        .file_name()
+        .ok_or_else(|| format!("{} has no file name", source.display()))?;
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let dest = dir.join(dedupe_name(dir, &name.to_string_lossy()));
+    let mut input = std::fs::File::open(source)
+        .map_err(|e| format!("Failed to open {}: {}", source.display(), e))?;
+    let mut output = std::fs::File::options()
+        .write(true)
+        .create_new(true)
+        .open(&dest)
+        .map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+    if let Err(e) = std::io::copy(&mut input, &mut output) {
+        drop(output);
+        let _ = std::fs::remove_file(&dest);
+        return Err(format!("Failed to copy {}: {}", source.display(), e));
+    }
+    Ok(dest)
+}
+
+/// Import `paths` into `dir`, one result per path in order. With `dedupe`,
+/// a file with the same contents as one already imported reuses that copy.
+fn import_files(dir: &Path, paths: Vec<String>, dedupe: bool) -> Vec<ImportedFile> {
+    // Content hash -> (original path, stored path) of the first import
+    let mut imported: HashMap<String, (String, PathBuf)> = HashMap::new();
+    let mut results = Vec::with_capacity(paths.len());
+    for original_path in paths {
+        let source = Path::new(&original_path);
+        let digest = if dedupe { hash_file(source).ok() } else { None };
+        if let Some((first, stored)) = digest.as_ref().and_then(|d| imported.get(d)) {
+            let first = Some(first.clone());
+            results.push(ImportedFile::stored(original_path, stored, first));
+            continue;
+        }
+
+        match import_file(dir, source) {
+            Ok(stored) => {
+                let result = ImportedFile::stored(original_path.clone(), &stored, None);
+                if let Some(digest) = digest {
+                    imported.insert(digest, (original_path, stored));
+                }
+                results.push(result);
+            }
+            Err(e) => {
+                log::warn!("[Attachments] Import failed: {}", e);
+                results.push(ImportedFile {
+                    original_path,
+                    stored_path: None,
+                    file_name: None,
+                    error: Some(e),
+                    duplicate_of: None,
+                });
+            }
+        }
+    }
+    results
+}
+
+/// Write clipboard pixels (RGBA, row by row) into `dir` as a PNG named after
+/// the time it was pasted
+fn save_clipboard_image(
+    dir: &Path,
+    rgba: Vec<u8>,
+    width: u32,
+    height: u32,
+) -> Result<PathBuf, String> {
+    let image = image::RgbaImage::from_raw(width, height, rgba).ok_or_else(|| {
+        format!(
+            "Clipboard image doesn't match its {}x{} size",
+            width, height
+        )
+    })?;
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut std::io::Cursor::new(&mut bytes), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode clipboard image: {}", e))?;
+
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let name = format!(
+        "Pasted image {}.png",
+        chrono::Local::now().format("%Y-%m-%d %H.%M.%S")
+    );
+    let dest = dir.join(dedupe_name(dir, &name));
+    let mut output = std::fs::File::options()
+        .write(true)
+        .create_new(true)
+        .open(&dest)
+        .map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+    if let Err(e) = output.write_all(&bytes) {
+        drop(output);
+        let _ = std::fs::remove_file(&dest);
+        return Err(format!("Failed to write {}: {}", dest.display(), e));
+    }
+    Ok(dest)
+}
+
+/// A new folder under `<data_dir>/attachments` for one batch of imports
+fn import_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(resolve_data_dir(app)?
+        .join(ATTACHMENTS_DIR)
+        .join(uuid::Uuid::new_v4().to_string()))
+}
+
+/// Check that `path` is a file or folder under one of `roots` that's safe to
+/// hand to its default app, returning it with symlinks resolved
+fn openable_path(path: &Path, roots: &[PathBuf]) -> Result<PathBuf, String> {
+    let resolved =
+        std::fs::canonicalize(path).map_err(|_| format!("{} doesn't exist", path.display()))?;
+    let inside = roots
+        .iter()
+        .filter_map(|root| std::fs::canonicalize(root).ok())
+        .any(|root| resolved.starts_with(root));
+    if !inside {
+        return Err(format!(
+            "{} is outside the folders attachments come from",
+            path.display()
+        ));
+    }
+    let extension = resolved
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase());
+    if extension.is_some_and(|e| LAUNCHABLE_EXTENSIONS.contains(&e.as_str())) {
+        return Err(format!("{} is a program, not opening it", path.display()));
+    }
+    Ok(resolved)
+}
+
+/// Open an attached file or folder in its default app. Only paths under the
+/// saved attachments, the temp folder or the user's desktop, documents,
+/// downloads and media folders are opened, and programs are refused rather
+/// than run.
+#[tauri::command]
+pub fn open_path(app: AppHandle, path: String) -> Result<(), String> {
+    let mut roots = vec![
+        resolve_data_dir(&app)?.join(ATTACHMENTS_DIR),
+        std::env::temp_dir(),
+    ];
+    let paths = app.path();
+    roots.extend(
+        [
+            paths.desktop_dir(),
+            paths.document_dir(),
+            paths.download_dir(),
+            paths.picture_dir(),
+            paths.audio_dir(),
+            paths.video_dir(),
+        ]
+        .into_iter()
+        .flatten(),
+    );
+    let path = crate::normalize_windows_path(openable_path(Path::new(&path), &roots)?);
+    app.opener()
+        .open_path(path.to_string_lossy(), None::<&str>)
+        .map_err(|e| format!("No app could open {}: {}", path.display(), e))
+}
+
+/// Show a file selected in its folder in Finder, Explorer or the Linux file
+/// manager. Works for attachments as well as the log and settings files.
+#[tauri::command]
+pub fn reveal_in_file_manager(app: AppHandle, path: String) -> Result<(), String> {
+    let path = std::fs::canonicalize(&path).map_err(|_| format!("{} doesn't exist", path))?;
+    let path = crate::normalize_windows_path(path);
+    app.opener()
+        .reveal_item_in_dir(&path)
+        .map_err(|e| format!("Failed to reveal {}: {}", path.display(), e))
+}
+
+/// Save an image on the clipboard, e.g. a screenshot, as a PNG in app
+/// storage alongside imported drops. Returns its metadata in the same form
+/// as `get_dropped_file_metadata`, or `None` if the clipboard holds no image.
+#[tauri::command]
+pub async fn attach_clipboard_image(app: AppHandle) -> Result<Option<AttachedFileInfo>, String> {
+    let image = match app.clipboard().read_image() {
+        Ok(image) => image,
+        Err(e) => {
+            log::debug!("[Attachments] No image on the clipboard: {}", e);
+            return Ok(None);
+        }
+    };
+    let (rgba, width, height) = (image.rgba().to_vec(), image.width(), image.height());
+    let dir = import_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let path = save_clipboard_image(&dir, rgba, width, height)?;
+        log::info!(
+            "[Attachments] Pasted {}x{} image to {}",
+            width,
+            height,
+            path.display()
+        );
+        AttachedFileInfo::read(&path, None, &DropOptions::default()).map(Some)
+    })
+    .await
+    .map_err(|e| format!("Saving clipboard image failed: {}", e))?
+}
+
+/// Copy dropped files into app storage, so they survive the originals being
+/// moved or deleted before the sidecar reads them. Each batch gets its own
+/// folder under `<data_dir>/attachments`, and a file that fails to copy
+/// doesn't stop the rest. With `dedupe`, files with identical contents are
+/// copied once.
+#[tauri::command]
+pub async fn import_dropped_files(
+    app: AppHandle,
+    paths: Vec<String>,
+    dedupe: Option<bool>,
+) -> Result<Vec<ImportedFile>, String> {
+    let dir = import_dir(&app)?;
+    let dedupe = dedupe.unwrap_or(false);
+    tauri::async_runtime::spawn_blocking(move || import_files(&dir, paths, dedupe))
+        .await
+        .map_err(|e| format!("Import failed: {}", e))
+}
+
+/// Read metadata for dropped files. Dropped folders are skipped unless
+/// `options.recurse` is set. Files over the size limit or outside the
+/// extension allowlist are returned marked as rejected, so partial batches
+/// still go through. With `options.dedupe`, repeated contents are marked
+/// with the first file that had them. Past `options.max_files`, files are
+/// counted but not read.
+#[tauri::command]
+pub async fn get_dropped_file_metadata(
+    paths: Vec<String>,
+    options: Option<DropOptions>,
+) -> Result<DroppedFiles, String> {
+    let options = Arc::new(options.unwrap_or_default());
+    let mut results = DroppedFiles::default();
+    let pending = collect_pending(paths, &options, &mut results);
+    if results.truncated {
+        log::warn!(
+            "[Attachments] Reading only the first {} of {} dropped files",
+            pending.len(),
+            results.total_files
+        );
+    }
+
+    // Read files in parallel on the blocking pool, then collect in drop order
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_READS));
+    let mut tasks = Vec::with_capacity(pending.len());
+    for file in pending {
+        let permit = permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| format!("Failed to queue file read: {}", e))?;
+        let options = options.clone();
+        tasks.push(tauri::async_runtime::spawn_blocking(move || {
+            let _permit = permit;
+            (read_pending(&file, &options), file.path)
+        }));
+    }
+    for task in tasks {
+        match task
+            .await
+            .map_err(|e| format!("Reading file failed: {}", e))?
+        {
+            (Ok(info), _) => results.files.push(info),
+            (Err(reason), path) => results.skip(&path, reason),
+        }
+    }
+    if options.dedupe {
+        mark_duplicates(&mut results.files);
+    }
+
+    Ok(results)
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DropSummary {
+    pub total_bytes: u64,
+    pub file_count: usize,
+    pub skipped_count: usize,
+    /// Whether `total_bytes` is over the budget, when one was given
+    pub over_budget: Option<bool>,
+}
+
+/// Add up the files in a drop. Files that vanish before they're measured
+/// count as skipped.
+fn summarize(
+    paths: Vec<String>,
+    options: &DropOptions,
+    max_total_bytes: Option<u64>,
+) -> DropSummary {
+    let mut results = DroppedFiles::default();
+    let pending = collect_pending(paths, options, &mut results);
+    let mut summary = DropSummary {
+        total_bytes: 0,
+        file_count: 0,
+        skipped_count: results.skipped.len(),
+        over_budget: None,
+    };
+    for file in pending {
+        match std::fs::metadata(&file.path) {
+            Ok(metadata) => {
+                summary.total_bytes += metadata.len();
+                summary.file_count += 1;
+            }
+            Err(_) => summary.skipped_count += 1,
+        }
+    }
+    summary.over_budget = max_total_bytes.map(|max| summary.total_bytes > max);
+    summary
+}
+
+/// Total size and count of the files in a drop, without reading them, so the
+/// UI can show what's about to be attached. Folders count their contents
+/// when `options.recurse` is set; `max_files` is ignored so the whole drop
+/// is counted.
+#[tauri::command]
+pub async fn get_dropped_files_summary(
+    paths: Vec<String>,
+    options: Option<DropOptions>,
+    max_total_bytes: Option<u64>,
+) -> Result<DropSummary, String> {
+    let options = DropOptions {
+        max_files: None,
+        ..options.unwrap_or_default()
+    };
+    tauri::async_runtime::spawn_blocking(move || summarize(paths, &options, max_total_bytes))
+        .await
+        .map_err(|e| format!("Summarizing drop failed: {}", e))
+}
+
+/// Existing files and folders among launch arguments, resolved against the
+/// launching shell's `cwd`. Flags and `pipali://` links are left out.
+pub fn file_args(args: &[String], cwd: &Path) -> Vec<String> {
+    args.iter()
+        .filter(|arg| !arg.starts_with('-') && !arg.starts_with("pipali://"))
+        .map(|arg| cwd.join(arg))
+        .filter(|path| path.exists())
+        .map(|path| path.to_string_lossy().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_dir;
+
+    #[test]
+    fn hashes_match_known_digests() {
+        let dir = test_dir("attachments-hash");
+        let empty = dir.join("empty");
+        std::fs::write(&empty, "").unwrap();
+        let abc = dir.join("abc.txt");
+        std::fs::write(&abc, "abc").unwrap();
+
+        assert_eq!(
+            hash_file(&empty).unwrap(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hash_file(&abc).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(hash_file(&dir.join("missing")).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    const PNG_HEADER: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[test]
+    fn contents_take_precedence_over_extension() {
+        let dir = test_dir("attachments-mime");
+        let mislabeled = dir.join("photo.txt");
+        std::fs::write(&mislabeled, PNG_HEADER).unwrap();
+        let notes = dir.join("notes.txt");
+        std::fs::write(&notes, "plain text").unwrap();
+        let blob = dir.join("blob");
+        std::fs::write(&blob, [0u8, 1, 2, 3]).unwrap();
+
+        assert_eq!(detect_mime_type(&mislabeled), "image/png");
+        assert_eq!(detect_mime_type(&notes), "text/plain");
+        assert_eq!(detect_mime_type(&blob), DEFAULT_MIME_TYPE);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn expands_folders_within_limits() {
+        let dir = test_dir("attachments-walk");
+        std::fs::create_dir_all(dir.join("a/b/c")).unwrap();
+        std::fs::write(dir.join("top.txt"), "1").unwrap();
+        std::fs::write(dir.join("a/one.txt"), "2").unwrap();
+        std::fs::write(dir.join("a/b/two.txt"), "3").unwrap();
+        std::fs::write(dir.join("a/b/c/three.txt"), "4").unwrap();
+
+        let walk = |max_depth, max_files| {
+            let mut pending = Vec::new();
+            let mut results = DroppedFiles::default();
+            let options = DropOptions {
+                max_depth,
+                max_files,
+                ..Default::default()
+            };
+            expand_dir(&dir, &dir, 0, &options, &mut pending, &mut results);
+            let found: Vec<_> = pending
+                .into_iter()
+                .map(|file| file.relative_path.unwrap())
+                .collect();
+            (found, results.truncated, results.total_files)
+        };
+
+        let all = ["a/b/c/three.txt", "a/b/two.txt", "a/one.txt", "top.txt"];
+        assert_eq!(walk(8, None), (all.map(String::from).to_vec(), false, 4));
+        assert_eq!(walk(1, None).0, ["a/one.txt", "top.txt"]);
+        let (found, truncated, total) = walk(8, Some(2));
+        assert_eq!(found, ["a/b/c/three.txt", "a/b/two.txt"]);
+        assert!(truncated);
+        assert_eq!(total, 4);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reads_dimensions_from_image_headers() {
+        let dir = test_dir("attachments-dimensions");
+        let png = dir.join("wide.png");
+        image::RgbImage::new(3, 2).save(&png).unwrap();
+        let truncated = dir.join("truncated.png");
+        std::fs::write(&truncated, PNG_HEADER).unwrap();
+        let notes = dir.join("notes.txt");
+        std::fs::write(&notes, "plain text").unwrap();
+
+        assert_eq!(image_dimensions(&png, "image/png"), Some((3, 2)));
+        assert_eq!(image_dimensions(&truncated, "image/png"), None);
+        assert_eq!(image_dimensions(&notes, "text/plain"), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn thumbnails_are_scaled_and_cached() {
+        let dir = test_dir("attachments-thumbnails");
+        let cache = dir.join("cache");
+        let photo = dir.join("photo.png");
+        image::RgbImage::new(40, 20).save(&photo).unwrap();
+        let notes = dir.join("notes.txt");
+        std::fs::write(&notes, "plain text").unwrap();
+
+        let url = thumbnail(&cache, &photo, 10).unwrap().unwrap();
+        let encoded = url.strip_prefix("data:image/jpeg;base64,").unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (10, 5));
+
+        let digest = hash_file(&photo).unwrap();
+        assert!(cache.join(format!("{}-10.jpg", digest)).is_file());
+        assert_eq!(thumbnail(&cache, &photo, 10).unwrap().unwrap(), url);
+        assert_eq!(thumbnail(&cache, &notes, 10).unwrap(), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn previews_stop_at_a_character_boundary() {
+        let dir = test_dir("attachments-preview");
+        let notes = dir.join("notes.txt");
+        std::fs::write(&notes, "naïve café").unwrap();
+
+        assert_eq!(text_preview(&notes, 3, UTF_8).as_deref(), Some("na"));
+        assert_eq!(text_preview(&notes, 4, UTF_8).as_deref(), Some("naï"));
+        assert_eq!(
+            text_preview(&notes, 1024, UTF_8).as_deref(),
+            Some("naïve café")
+        );
+
+        let utf16 = dir.join("utf16.log");
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend("héllo".encode_utf16().flat_map(|u| u.to_le_bytes()));
+        std::fs::write(&utf16, bytes).unwrap();
+        let encoding = encoding_rs::UTF_16LE;
+        assert_eq!(text_preview(&utf16, 7, encoding).as_deref(), Some("hé"));
+        assert_eq!(
+            text_preview(&utf16, 1024, encoding).as_deref(),
+            Some("héllo")
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_symlinks_but_skips_broken_and_circular_ones() {
+        use std::os::unix::fs::symlink;
+
+        let dir = test_dir("attachments-symlinks");
+        std::fs::create_dir_all(dir.join("docs")).unwrap();
+        std::fs::write(dir.join("docs/real.txt"), "contents").unwrap();
+        symlink(dir.join("docs/real.txt"), dir.join("docs/link.txt")).unwrap();
+        symlink(dir.join("missing"), dir.join("docs/broken")).unwrap();
+        symlink(&dir, dir.join("docs/up")).unwrap();
+
+        let options = DropOptions {
+            recurse: true,
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        let docs = dir.join("docs").to_string_lossy().to_string();
+        let results =
+            tauri::async_runtime::block_on(get_dropped_file_metadata(vec![docs], Some(options)))
+                .unwrap();
+
+        let real = std::fs::canonicalize(dir.join("docs/real.txt")).unwrap();
+        let link = &results.files[0];
+        assert_eq!(link.file_name, "link.txt");
+        assert_eq!(link.resolved_path.as_deref(), Some(real.to_str().unwrap()));
+        assert_eq!(link.size_bytes, 8);
+        assert_eq!(results.files.len(), 2);
+
+        let mut skipped: Vec<_> = results.skipped.iter().map(|s| s.path.as_str()).collect();
+        skipped.sort();
+        let broken = dir.join("docs/broken");
+        let up = dir.join("docs/up");
+        assert_eq!(skipped, [broken.to_str().unwrap(), up.to_str().unwrap()]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_files_over_the_size_limit() {
+        let dir = test_dir("attachments-size");
+        let small = dir.join("small.txt");
+        std::fs::write(&small, "1234").unwrap();
+        let large = dir.join("large.txt");
+        std::fs::write(&large, "12345").unwrap();
+        let options = DropOptions {
+            max_size_bytes: Some(4),
+            ..Default::default()
+        };
+
+        let small = AttachedFileInfo::read(&small, None, &options).unwrap();
+        assert!(!small.rejected);
+        assert_eq!(small.reason, None);
+        let large = AttachedFileInfo::read(&large, None, &options).unwrap();
+        assert!(large.rejected);
+        assert!(large.reason.unwrap().contains("4 byte limit"));
+
+        let unlimited = DropOptions::default();
+        let large = AttachedFileInfo::read(&dir.join("large.txt"), None, &unlimited).unwrap();
+        assert!(!large.rejected);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn allowlist_matches_extensions_in_any_case() {
+        let options = DropOptions {
+            allowed_extensions: vec!["PDF".to_string(), ".md".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(options.rejection(Path::new("report.pdf"), 0), None);
+        assert_eq!(options.rejection(Path::new("README.MD"), 0), None);
+        assert_eq!(
+            options.rejection(Path::new("photo.png"), 0).as_deref(),
+            Some(".png files aren't allowed")
+        );
+        assert!(options.rejection(Path::new("Makefile"), 0).is_some());
+
+        let extensionless = DropOptions {
+            allowed_extensions: vec![String::new()],
+            ..Default::default()
+        };
+        assert_eq!(extensionless.rejection(Path::new("Makefile"), 0), None);
+        assert_eq!(
+            DropOptions::default().rejection(Path::new("a.exe"), 0),
+            None
+        );
+    }
+
+    #[test]
+    fn large_drops_keep_their_order() {
+        let dir = test_dir("attachments-many");
+        // Reverse name order and varying sizes, so sorted or out-of-order
+        // results would show up
+        let paths: Vec<String> = (0..300)
+            .rev()
+            .map(|i| {
+                let path = dir.join(format!("file-{:03}.txt", i));
+                std::fs::write(&path, "x".repeat(i)).unwrap();
+                path.to_string_lossy().to_string()
+            })
+            .collect();
+        let options = DropOptions {
+            hash: true,
+            ..Default::default()
+        };
+
+        let results =
+            tauri::async_runtime::block_on(get_dropped_file_metadata(paths.clone(), Some(options)))
+                .unwrap();
+
+        assert!(results.skipped.is_empty());
+        let returned: Vec<_> = results.files.iter().map(|f| f.file_path.clone()).collect();
+        assert_eq!(returned, paths);
+        for (info, i) in results.files.iter().zip((0..300u64).rev()) {
+            assert_eq!(info.size_bytes, i);
+            assert!(info.sha256.is_some());
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn binary_files_are_told_apart_from_text() {
+        let name = |sample: &[u8]| detect_encoding(sample).map(|e| e.name());
+        assert_eq!(name(b""), Some("UTF-8"));
+        assert_eq!(name("naïve café\n".as_bytes()), Some("UTF-8"));
+        assert_eq!(name(b"\xff\xfeh\0i\0"), Some("UTF-16LE"));
+        assert_eq!(name(b"\xfe\xff\0h\0i"), Some("UTF-16BE"));
+        assert_eq!(
+            name(b"Le caf\xe9 \xe9tait ferm\xe9, nous sommes all\xe9s \xe0 la plage."),
+            Some("windows-1252")
+        );
+        assert_eq!(name(PNG_HEADER), None);
+    }
+
+    #[test]
+    fn timestamps_are_epoch_millis() {
+        let time = UNIX_EPOCH + std::time::Duration::from_millis(1_700_000_000_123);
+        assert_eq!(epoch_millis(Ok(time)), Some(1_700_000_000_123));
+        let before_epoch = UNIX_EPOCH - std::time::Duration::from_secs(1);
+        assert_eq!(epoch_millis(Ok(before_epoch)), None);
+        let unsupported = std::io::Error::new(std::io::ErrorKind::Unsupported, "no birth time");
+        assert_eq!(epoch_millis(Err(unsupported)), None);
+    }
+
+    #[test]
+    fn imports_number_clashing_names_instead_of_overwriting() {
+        let dir = test_dir("attachments-import");
+        let store = dir.join("store");
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+        std::fs::create_dir_all(dir.join("b")).unwrap();
+        std::fs::write(dir.join("a/notes.txt"), "first").unwrap();
+        std::fs::write(dir.join("b/notes.txt"), "second").unwrap();
+        let path = |p: &str| dir.join(p).to_string_lossy().to_string();
+
+        let results = import_files(
+            &store,
+            vec![
+                path("a/notes.txt"),
+                path("missing.txt"),
+                path("b/notes.txt"),
+            ],
+            false,
+        );
+
+        let stored = results[0].stored_path.as_deref().unwrap();
+        assert_eq!(std::fs::read_to_string(stored).unwrap(), "first");
+        assert!(results[1].error.is_some());
+        assert_eq!(results[2].original_path, path("b/notes.txt"));
+        assert_eq!(results[2].file_name.as_deref(), Some("notes (1).txt"));
+        assert_eq!(
+            std::fs::read_to_string(store.join("notes.txt")).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            std::fs::read_to_string(store.join("notes (1).txt")).unwrap(),
+            "second"
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn duplicates_point_at_the_first_copy() {
+        let dir = test_dir("attachments-dedupe");
+        let store = dir.join("store");
+        std::fs::write(dir.join("one.txt"), "same").unwrap();
+        std::fs::write(dir.join("two.txt"), "other").unwrap();
+        std::fs::write(dir.join("copy.txt"), "same").unwrap();
+        let paths: Vec<String> = ["one.txt", "two.txt", "copy.txt", "one.txt"]
+            .iter()
+            .map(|p| dir.join(p).to_string_lossy().to_string())
+            .collect();
+        let options = DropOptions {
+            dedupe: true,
+            ..Default::default()
+        };
+
+        let metadata =
+            tauri::async_runtime::block_on(get_dropped_file_metadata(paths.clone(), Some(options)))
+                .unwrap();
+        let duplicates: Vec<_> = metadata
+            .files
+            .iter()
+            .map(|f| f.duplicate_of.clone())
+            .collect();
+        let first = Some(paths[0].clone());
+        assert_eq!(duplicates, [None, None, first.clone(), first.clone()]);
+
+        let imported = import_files(&store, paths, true);
+        assert_eq!(imported[2].duplicate_of, first);
+        assert_eq!(imported[2].stored_path, imported[0].stored_path);
+        assert_eq!(imported[3].stored_path, imported[0].stored_path);
+        assert_eq!(std::fs::read_dir(&store).unwrap().count(), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn summaries_add_up_folder_contents() {
+        let dir = test_dir("attachments-summary");
+        std::fs::create_dir_all(dir.join("folder/nested")).unwrap();
+        std::fs::write(dir.join("loose.txt"), "12345").unwrap();
+        std::fs::write(dir.join("folder/a.txt"), "123").unwrap();
+        std::fs::write(dir.join("folder/nested/b.txt"), "12").unwrap();
+        let paths: Vec<String> = ["loose.txt", "folder"]
+            .iter()
+            .map(|p| dir.join(p).to_string_lossy().to_string())
+            .collect();
+        let recurse = DropOptions {
+            recurse: true,
+            ..Default::default()
+        };
+
+        let summary = summarize(paths.clone(), &recurse, Some(10));
+        assert_eq!(
+            summary,
+            DropSummary {
+                total_bytes: 10,
+                file_count: 3,
+                skipped_count: 0,
+                over_budget: Some(false),
+            }
+        );
+        assert_eq!(
+            summarize(paths.clone(), &recurse, Some(9)).over_budget,
+            Some(true)
+        );
+        let flat = summarize(paths, &DropOptions::default(), None);
+        assert_eq!(
+            (flat.total_bytes, flat.file_count, flat.over_budget),
+            (5, 1, None)
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn dedupe_name_numbers_before_the_extension() {
+        let dir = test_dir("attachments-names");
+        assert_eq!(dedupe_name(&dir, "report.pdf"), "report.pdf");
+
+        for name in [
+            "report.pdf",
+            "report (1).pdf",
+            "Makefile",
+            "archive.tar.gz",
+            ".env",
+        ] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        assert_eq!(dedupe_name(&dir, "report.pdf"), "report (2).pdf");
+        assert_eq!(dedupe_name(&dir, "Makefile"), "Makefile (1)");
+        assert_eq!(dedupe_name(&dir, "archive.tar.gz"), "archive.tar (1).gz");
+        assert_eq!(dedupe_name(&dir, ".env"), ".env (1)");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn launch_args_keep_only_existing_paths() {
+        let dir = test_dir("attachments-args");
+        std::fs::write(dir.join("notes.txt"), "hi").unwrap();
+        let absolute = dir.join("notes.txt").to_string_lossy().to_string();
+        let args: Vec<String> = [
+            "notes.txt",
+            "missing.txt",
+            "--launched-at-login",
+            "pipali://chat/1",
+            absolute.as_str(),
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+        assert_eq!(file_args(&args, &dir), vec![absolute.clone(), absolute]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn clipboard_images_are_saved_as_png() {
+        let dir = test_dir("attachments-clipboard");
+        let pixels = vec![255, 0, 0, 255, 0, 0, 255, 128];
+        let first = save_clipboard_image(&dir, pixels.clone(), 2, 1).unwrap();
+        let second = save_clipboard_image(&dir, pixels, 2, 1).unwrap();
+        assert_ne!(first, second);
+
+        let info = AttachedFileInfo::read(&first, None, &DropOptions::default()).unwrap();
+        assert_eq!(info.mime_type, "image/png");
+        assert_eq!((info.width, info.height), (Some(2), Some(1)));
+        assert!(save_clipboard_image(&dir, vec![0; 3], 2, 1).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn only_paths_under_known_folders_open() {
+        let dir = test_dir("attachments-open");
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("report.pdf"), "pdf").unwrap();
+        std::fs::write(root.join("setup.EXE"), "exe").unwrap();
+        std::fs::write(root.join("Editor.AppImage"), "elf").unwrap();
+        // A macOS app bundle is a folder
+        std::fs::create_dir_all(root.join("Tool.app").join("Contents")).unwrap();
+        std::fs::write(dir.join("elsewhere.txt"), "txt").unwrap();
+        let roots = [root.clone()];
+
+        assert!(openable_path(&root.join("report.pdf"), &roots).is_ok());
+        assert!(openable_path(&root, &roots).is_ok());
+        // Escaping the root through `..` is caught once the path is resolved
+        assert!(openable_path(&root.join("../elsewhere.txt"), &roots).is_err());
+        assert!(openable_path(&root.join("missing.pdf"), &roots).is_err());
+        assert!(openable_path(&root.join("setup.EXE"), &roots).is_err());
+        assert!(openable_path(&root.join("Editor.AppImage"), &roots).is_err());
+        assert!(openable_path(&root.join("Tool.app"), &roots).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
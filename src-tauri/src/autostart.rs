@@ -0,0 +1,75 @@
+use tauri::{AppHandle, State};
+use tauri_plugin_autostart::ManagerExt;
+
+use crate::settings::SettingsStore;
+
+/// Passed by the OS login item so a launch at login can be told apart from
+/// the user opening the app
+pub const LAUNCHED_AT_LOGIN_ARG: &str = "--launched-at-login";
+
+/// Whether the OS will start the app at login
+fn is_registered(app: &AppHandle) -> Result<bool, String> {
+    app.autolaunch()
+        .is_enabled()
+        .map_err(|e| format!("Failed to read launch at login: {}", e))
+}
+
+/// Add or remove the login item: a LaunchAgent on macOS, the `Run` registry
+/// key on Windows, an autostart .desktop entry on Linux
+fn register(app: &AppHandle, enabled: bool) -> Result<(), String> {
+    let manager = app.autolaunch();
+    let result = if enabled {
+        manager.enable()
+    } else {
+        manager.disable()
+    };
+    result.map_err(|e| format!("Failed to update launch at login: {}", e))
+}
+
+/// Bring the OS login item in line with the saved choice, e.g. after the
+/// entry was removed by hand or the app was moved
+pub fn sync_launch_at_login(app: &AppHandle, store: &SettingsStore) {
+    let Some(enabled) = store.settings().launch_at_login else {
+        return;
+    };
+    match is_registered(app) {
+        Ok(registered) if registered == enabled => {}
+        Ok(_) => match register(app, enabled) {
+            Ok(()) => log::info!("[App] Re-registered launch at login: {}", enabled),
+            Err(e) => log::warn!("[App] {}", e),
+        },
+        Err(e) => log::warn!("[App] {}", e),
+    }
+}
+
+/// Whether this launch came from the login item and should stay in the
+/// tray. Needs both `start_minimized` (on by default) and `close_to_tray`,
+/// so the app only starts hidden where it can live in the tray.
+pub fn should_start_hidden(store: &SettingsStore) -> bool {
+    let settings = store.settings();
+    std::env::args().any(|arg| arg == LAUNCHED_AT_LOGIN_ARG)
+        && settings.start_minimized.unwrap_or(true)
+        && settings.close_to_tray.unwrap_or(true)
+}
+
+/// Start the app when the user logs in, or stop doing so
+#[tauri::command]
+pub fn set_launch_at_login(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    enabled: bool,
+) -> Result<(), String> {
+    register(&app, enabled)?;
+    store.update(|settings| settings.launch_at_login = Some(enabled))?;
+    log::info!(
+        "[App] Launch at login {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(())
+}
+
+/// Whether the app is set to start at login, as registered with the OS
+#[tauri::command]
+pub fn get_launch_at_login(app: AppHandle) -> Result<bool, String> {
+    is_registered(&app)
+}
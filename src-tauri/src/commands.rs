@@ -1,51 +1,202 @@
-use std::time::Duration;
 use serde::Serialize;
-use tauri::{AppHandle, State};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State, UserAttentionType, WebviewUrl, WebviewWindowBuilder};
+use tauri_plugin_opener::OpenerExt;
 
-use crate::{show_window, start_sidecar, stop_sidecar, SidecarState};
+use crate::settings::SettingsStore;
+use crate::show_window;
+use crate::sidecar::{
+    self, Sidecar, SidecarCrash, SidecarStartError, SidecarState, SidecarStatus, SidecarVersion,
+    DEFAULT_SIDECAR,
+};
+use crate::sidecar_resources::SidecarResources;
+use crate::window_opacity;
+use crate::window_state;
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct AttachedFileInfo {
-    pub file_path: String,
-    pub file_name: String,
-    pub size_bytes: u64,
-}
-
-#[derive(Serialize)]
 pub struct SidecarConfig {
     pub host: String,
     pub port: u16,
+    /// Interface the sidecar listens on, `127.0.0.1` unless configured otherwise
+    pub bind_address: String,
+}
+
+/// Sidecar instance named by a command, `"default"` when none was given
+fn sidecar_name(name: &Option<String>) -> &str {
+    name.as_deref().unwrap_or(DEFAULT_SIDECAR)
+}
+
+/// A sidecar instance that has been started at least once
+fn find_sidecar(state: &SidecarState, name: &Option<String>) -> Result<Arc<Sidecar>, String> {
+    let name = sidecar_name(name);
+    state
+        .find(name)
+        .ok_or_else(|| format!("Unknown sidecar: {}", name))
 }
 
 /// Get the sidecar port (exposed to frontend)
 #[tauri::command]
-pub fn get_sidecar_port(state: State<'_, SidecarState>) -> u16 {
-    state.port
+pub fn get_sidecar_port(
+    state: State<'_, SidecarState>,
+    name: Option<String>,
+) -> Result<u16, String> {
+    Ok(find_sidecar(&state, &name)?.port())
 }
 
 /// Get the sidecar host (exposed to frontend)
 #[tauri::command]
-pub fn get_sidecar_host(state: State<'_, SidecarState>) -> String {
-    state.host.clone()
+pub fn get_sidecar_host(
+    state: State<'_, SidecarState>,
+    name: Option<String>,
+) -> Result<String, String> {
+    Ok(find_sidecar(&state, &name)?.host())
 }
 
 /// Get the sidecar config (host and port) - exposed to frontend
 #[tauri::command]
-pub fn get_sidecar_config(state: State<'_, SidecarState>) -> SidecarConfig {
-    SidecarConfig {
-        host: state.host.clone(),
-        port: state.port,
-    }
+pub fn get_sidecar_config(
+    state: State<'_, SidecarState>,
+    name: Option<String>,
+) -> Result<SidecarConfig, String> {
+    let sidecar = find_sidecar(&state, &name)?;
+    Ok(SidecarConfig {
+        host: sidecar.host(),
+        port: sidecar.port(),
+        bind_address: sidecar.bind_address(),
+    })
+}
+
+/// Get the status of every sidecar instance (exposed to frontend)
+#[tauri::command]
+pub fn list_sidecars(state: State<'_, SidecarState>) -> Vec<SidecarStatus> {
+    state
+        .instances()
+        .iter()
+        .map(|sidecar| sidecar.status())
+        .collect()
+}
+
+/// Start a sidecar and wait for it to come up (exposed to frontend). Does
+/// nothing but wait if it is already running.
+#[tauri::command]
+pub async fn start_sidecar(app: AppHandle, name: Option<String>) -> Result<(), SidecarStartError> {
+    let name = sidecar_name(&name);
+    sidecar::start_sidecar(&app, name)?;
+    sidecar::wait_for_started_sidecar(&app, name).await
+}
+
+/// Stop a sidecar (exposed to frontend)
+#[tauri::command]
+pub async fn stop_sidecar(app: AppHandle, name: Option<String>) -> Result<(), String> {
+    sidecar::stop_sidecar(&app, sidecar_name(&name)).await
 }
 
 /// Restart the sidecar (exposed to frontend)
+///
+/// Resolves once the new server answers its health check, or fails with a
+/// `SidecarStartError` saying why it never came up.
+#[tauri::command]
+pub async fn restart_sidecar(
+    app: AppHandle,
+    name: Option<String>,
+) -> Result<(), SidecarStartError> {
+    sidecar::restart_sidecar(&app, sidecar_name(&name)).await
+}
+
+/// Leave the `Failed` phase and try one more start (exposed to frontend)
+#[tauri::command]
+pub async fn retry_sidecar(app: AppHandle, name: Option<String>) -> Result<(), SidecarStartError> {
+    let name = sidecar_name(&name);
+    log::info!("[Sidecar] Retrying start of {}", name);
+    // Clear the restart history, so a failed sidecar gets a fresh set of
+    // automatic restarts
+    app.state::<SidecarState>()
+        .instance(name)
+        .reset_restart_history();
+    sidecar::start_sidecar(&app, name)?;
+    sidecar::wait_for_started_sidecar(&app, name).await
+}
+
+/// Get the sidecar's lifecycle phase, last exit code and restart count
+/// (exposed to frontend). Changes are also pushed as `sidecar-status` events.
+#[tauri::command]
+pub fn get_sidecar_status(
+    state: State<'_, SidecarState>,
+    name: Option<String>,
+) -> Result<SidecarStatus, String> {
+    Ok(find_sidecar(&state, &name)?.status())
+}
+
+/// Get the sidecar's most recent unexpected exits, oldest first (exposed to
+/// frontend). New ones are also pushed as `sidecar-crashed` events.
+#[tauri::command]
+pub fn get_sidecar_crashes(
+    state: State<'_, SidecarState>,
+    name: Option<String>,
+) -> Result<Vec<SidecarCrash>, String> {
+    Ok(find_sidecar(&state, &name)?.crashes())
+}
+
+/// Get the absolute path of the sidecar's log file (exposed to frontend)
+#[tauri::command]
+pub fn get_sidecar_log_path(app: AppHandle, name: Option<String>) -> Result<String, String> {
+    let path = sidecar::sidecar_log_path(&app, sidecar_name(&name))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Show the sidecar's log file in the system file manager (exposed to frontend)
+#[tauri::command]
+pub fn reveal_sidecar_log(app: AppHandle, name: Option<String>) -> Result<(), String> {
+    let path = sidecar::sidecar_log_path(&app, sidecar_name(&name))?;
+    // The file manager can only select a file that exists
+    if !path.exists() {
+        std::fs::File::create(&path)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    }
+    app.opener()
+        .reveal_item_in_dir(&path)
+        .map_err(|e| format!("Failed to reveal {}: {}", path.display(), e))
+}
+
+/// Empty the sidecar's log file and delete its rotated copies (exposed to frontend)
 #[tauri::command]
-pub async fn restart_sidecar(app: AppHandle) -> Result<(), String> {
-    stop_sidecar(&app)?;
-    // Small delay to ensure clean shutdown
-    std::thread::sleep(Duration::from_millis(500));
-    start_sidecar(&app)
+pub fn clear_sidecar_logs(app: AppHandle, name: Option<String>) -> Result<(), String> {
+    sidecar::clear_sidecar_logs(&app, sidecar_name(&name))
+}
+
+/// Get the version and commit of the running sidecar (exposed to frontend)
+#[tauri::command]
+pub async fn get_sidecar_version(
+    state: State<'_, SidecarState>,
+    name: Option<String>,
+) -> Result<SidecarVersion, String> {
+    let sidecar = find_sidecar(&state, &name)?;
+    sidecar.version().await
+}
+
+/// Get the latest CPU, memory and uptime sample of a running sidecar
+/// (exposed to frontend)
+#[tauri::command]
+pub fn get_sidecar_resources(
+    state: State<'_, SidecarState>,
+    name: Option<String>,
+) -> Result<SidecarResources, String> {
+    let sidecar = find_sidecar(&state, &name)?;
+    sidecar
+        .resources()
+        .ok_or_else(|| format!("Sidecar {} is not running", sidecar.name()))
+}
+
+/// Forward sidecar output to the frontend as `sidecar-log` events while a
+/// debug console is open (exposed to frontend)
+#[tauri::command]
+pub fn enable_sidecar_log_streaming(state: State<'_, SidecarState>, enabled: bool) {
+    state.set_log_streaming(enabled);
+    log::info!(
+        "[Sidecar] Log streaming {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
 }
 
 /// Show the app window and add it to the dock (exposed to frontend)
@@ -54,30 +205,126 @@ pub fn focus_window(app: AppHandle) {
     show_window(&app);
 }
 
-/// Read metadata for dropped files
+/// Hide the app window to the tray (exposed to frontend). On macOS the app
+/// also leaves the dock unless `remove_from_dock` is false.
 #[tauri::command]
-pub async fn get_dropped_file_metadata(paths: Vec<String>) -> Result<Vec<AttachedFileInfo>, String> {
-    let mut results = Vec::new();
-    for source_path_str in paths {
-        let source = std::path::PathBuf::from(&source_path_str);
-        if !source.exists() || !source.is_file() {
-            continue;
-        }
+pub fn hide_window(app: AppHandle, remove_from_dock: Option<bool>) {
+    crate::hide_window(&app, remove_from_dock.unwrap_or(true));
+}
 
-        let file_name = source
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| "unknown".to_string());
+/// Flash the taskbar button or bounce the dock icon, e.g. when a task
+/// finishes in the background. Critical keeps going until the app is
+/// focused; informational flashes once. Nothing happens if the window is
+/// already focused.
+#[tauri::command]
+pub fn request_user_attention(app: AppHandle, critical: bool) -> Result<(), String> {
+    let Some(window) = app.get_webview_window("main") else {
+        return Ok(());
+    };
+    if window.is_focused().unwrap_or(false) {
+        return Ok(());
+    }
+    let kind = if critical {
+        UserAttentionType::Critical
+    } else {
+        UserAttentionType::Informational
+    };
+    window
+        .request_user_attention(Some(kind))
+        .map_err(|e| format!("Failed to request attention: {}", e))
+}
 
-        let metadata = std::fs::metadata(&source)
-            .map_err(|e| format!("Failed to read metadata for {}: {}", source_path_str, e))?;
+/// Show or hide the macOS dock icon. While hidden the app is still reachable
+/// from the tray. Does nothing on other platforms.
+#[tauri::command]
+pub fn set_dock_visible(app: AppHandle, visible: bool) {
+    if visible {
+        crate::show_in_dock(&app);
+    } else {
+        crate::hide_from_dock(&app);
+    }
+}
 
-        results.push(AttachedFileInfo {
-            file_path: source_path_str,
-            file_name,
-            size_bytes: metadata.len(),
-        });
+/// Label prefix of windows opened by `open_window`, which only get the
+/// narrower `secondary` capability
+const SECONDARY_WINDOW_PREFIX: &str = "secondary-";
+
+/// Open a secondary window on an app route, e.g. `/logs`, or focus it if a
+/// window with that label is already open. Labels must start with
+/// `secondary-`. Closing it leaves the main window alone.
+// Async because building a window from a sync command deadlocks on Windows
+#[tauri::command]
+pub async fn open_window(app: AppHandle, label: String, url: String) -> Result<(), String> {
+    if !label.starts_with(SECONDARY_WINDOW_PREFIX) {
+        return Err(format!(
+            "Window label '{}' must start with '{}'",
+            label, SECONDARY_WINDOW_PREFIX
+        ));
+    }
+    if url.contains("://") || url.starts_with("//") {
+        return Err(format!(
+            "Secondary windows only open app routes, got {}",
+            url
+        ));
     }
 
-    Ok(results)
+    if let Some(window) = app.get_webview_window(&label) {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+        return Ok(());
+    }
+    let window = WebviewWindowBuilder::new(&app, &label, WebviewUrl::App(url.into()))
+        .title("Pipali")
+        .inner_size(900.0, 600.0)
+        .min_inner_size(400.0, 300.0)
+        .resizable(true)
+        .build()
+        .map_err(|e| format!("Failed to open window '{}': {}", label, e))?;
+    window_state::apply_zoom(&window, &app.state::<SettingsStore>());
+    log::info!("[App] Opened window '{}'", label);
+    Ok(())
+}
+
+/// Zoom the app's windows and remember the factor for next launch. Returns
+/// the factor actually applied, clamped to 0.5-3.0.
+#[tauri::command]
+pub fn set_zoom(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    factor: f64,
+) -> Result<f64, String> {
+    window_state::set_zoom(&app, &store, factor)
+}
+
+/// The current webview zoom factor
+#[tauri::command]
+pub fn get_zoom(store: State<'_, SettingsStore>) -> f64 {
+    window_state::zoom(&store)
+}
+
+/// Enter or leave fullscreen. Returns the new state.
+#[tauri::command]
+pub fn toggle_fullscreen(app: AppHandle, store: State<'_, SettingsStore>) -> Result<bool, String> {
+    window_state::toggle_fullscreen(&app, &store)
+}
+
+/// Make the app window see-through, from 0.2 to fully opaque at 1.0, and
+/// remember it for next launch. Returns the opacity actually applied.
+#[tauri::command]
+pub fn set_window_opacity(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    opacity: f64,
+) -> Result<f64, String> {
+    window_opacity::set_window_opacity(&app, &store, opacity)
+}
+
+/// Pin the app window above other windows, or unpin it. Returns the new state.
+#[tauri::command]
+pub fn toggle_always_on_top(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+) -> Result<bool, String> {
+    window_state::toggle_always_on_top(&app, &store)
 }
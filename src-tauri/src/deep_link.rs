@@ -0,0 +1,85 @@
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+
+use crate::{notifications, show_window};
+
+/// Links sent to the frontend as `deep-link` events
+const SCHEME: &str = "pipali";
+
+/// Deep links that arrive before the frontend is listening, e.g. the link
+/// the app was launched with, are held here until it asks for them
+#[derive(Default)]
+pub struct DeepLinkState {
+    inner: Mutex<PendingLinks>,
+}
+
+#[derive(Default)]
+struct PendingLinks {
+    frontend_ready: bool,
+    urls: Vec<String>,
+}
+
+/// Route a `pipali://` link to the frontend and bring the window up
+fn handle_deep_link(app: &AppHandle, url: &Url) {
+    if url.scheme() != SCHEME {
+        log::warn!("[App] Ignoring link with unexpected scheme: {}", url);
+        return;
+    }
+    log::info!("[App] Deep link received: {}", url);
+    let state: State<DeepLinkState> = app.state();
+    let mut pending = state.inner.lock().unwrap();
+    if !pending.frontend_ready {
+        // Still starting up: the splash hands over to the main window itself
+        notifications::clear_pending_click(app);
+        if !pending.urls.contains(&url.to_string()) {
+            pending.urls.push(url.to_string());
+        }
+        return;
+    }
+    drop(pending);
+    let _ = app.emit("deep-link", url.to_string());
+    show_window(app);
+}
+
+/// Pick up the link the app was launched with and listen for more. Links
+/// opened while running reach this instance through the single-instance
+/// plugin on Windows and Linux, and straight from the OS on macOS.
+pub fn init_deep_links(app: &AppHandle) {
+    // An AppImage or dev build isn't registered as the handler by an installer
+    #[cfg(target_os = "linux")]
+    let unregistered = app.env().appimage.is_some();
+    #[cfg(windows)]
+    let unregistered = cfg!(debug_assertions);
+    #[cfg(any(target_os = "linux", windows))]
+    if unregistered {
+        if let Err(e) = app.deep_link().register_all() {
+            log::warn!("[App] Failed to register {}:// links: {}", SCHEME, e);
+        }
+    }
+
+    match app.deep_link().get_current() {
+        Ok(urls) => {
+            for url in urls.unwrap_or_default() {
+                handle_deep_link(app, &url);
+            }
+        }
+        Err(e) => log::warn!("[App] Failed to read launch deep link: {}", e),
+    }
+
+    let app_handle = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            handle_deep_link(&app_handle, &url);
+        }
+    });
+}
+
+/// Deep links received before the frontend was listening. Once called, new
+/// links are emitted as `deep-link` events instead.
+#[tauri::command]
+pub fn take_pending_deep_links(state: State<'_, DeepLinkState>) -> Vec<String> {
+    let mut pending = state.inner.lock().unwrap();
+    pending.frontend_ready = true;
+    std::mem::take(&mut pending.urls)
+}
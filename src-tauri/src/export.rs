@@ -0,0 +1,105 @@
+use std::io::Write;
+use std::path::Path;
+use tauri::AppHandle;
+use tauri_plugin_dialog::DialogExt;
+
+/// Write `contents` to `path` through a temporary file in the same folder,
+/// so a failed write leaves neither a partial file nor a damaged original
+pub fn write_file_atomically(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file path", path.display()))?;
+    let tmp_path = dir.join(format!(
+        ".{}.{}.partial",
+        name.to_string_lossy(),
+        uuid::Uuid::new_v4()
+    ));
+
+    let result = std::fs::File::create(&tmp_path)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| std::fs::rename(&tmp_path, path));
+    result.map_err(|e| {
+        let _ = std::fs::remove_file(&tmp_path);
+        format!("Couldn't save {}: {}", path.display(), e)
+    })
+}
+
+/// Ask where to save with the native save dialog, then write `contents`
+/// there. Returns the chosen path, or `None` if the dialog was cancelled.
+pub fn save_with_dialog(
+    app: &AppHandle,
+    title: &str,
+    default_name: &str,
+    filter: Option<(&str, &[&str])>,
+    contents: &[u8],
+) -> Result<Option<String>, String> {
+    let mut dialog = app
+        .dialog()
+        .file()
+        .set_title(title)
+        .set_file_name(default_name);
+    if let Some((name, extensions)) = filter {
+        dialog = dialog.add_filter(name, extensions);
+    }
+    let Some(path) = dialog.blocking_save_file() else {
+        return Ok(None);
+    };
+    let path = path
+        .into_path()
+        .map_err(|e| format!("Unusable save location: {}", e))?;
+    write_file_atomically(&path, contents)?;
+    log::info!(
+        "[Export] Saved {} bytes to {}",
+        contents.len(),
+        path.display()
+    );
+    Ok(Some(path.to_string_lossy().to_string()))
+}
+
+/// Let the user pick where to save exported content, such as logs or
+/// generated output, and write it there. Returns the saved path, or `None`
+/// if the dialog was cancelled.
+#[tauri::command]
+pub async fn save_file_dialog(
+    app: AppHandle,
+    default_name: String,
+    contents: Vec<u8>,
+) -> Result<Option<String>, String> {
+    save_with_dialog(&app, "Save As", &default_name, None, &contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_dir;
+
+    #[test]
+    fn atomic_writes_replace_or_leave_nothing_behind() {
+        let dir = test_dir("export");
+
+        let path = dir.join("notes.txt");
+        write_file_atomically(&path, b"first").unwrap();
+        write_file_atomically(&path, b"second").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+
+        // A folder in the way makes the rename fail
+        let blocked = dir.join("blocked");
+        std::fs::create_dir_all(blocked.join("inside")).unwrap();
+        assert!(write_file_atomically(&blocked, b"data").is_err());
+        assert!(write_file_atomically(&dir.join("missing/notes.txt"), b"data").is_err());
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["blocked", "notes.txt"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,14 +1,28 @@
+mod attachments;
+mod autostart;
 mod commands;
+mod deep_link;
+mod export;
+mod logging;
+mod notifications;
+mod settings;
+mod shortcut;
+mod sidecar;
+mod sidecar_log;
+mod sidecar_pid;
+mod sidecar_priority;
+mod sidecar_resources;
+#[cfg(test)]
+mod test_support;
 mod wake_lock;
+mod window_opacity;
+mod window_state;
 
-use std::sync::Mutex;
+use sidecar::{SidecarState, DEFAULT_SIDECAR};
 use std::time::Duration;
-use std::time::Instant;
 use tauri::menu::{CheckMenuItemBuilder, MenuBuilder, MenuItemBuilder};
 use tauri::tray::{MouseButton, MouseButtonState, TrayIconEvent};
-use tauri::{AppHandle, Emitter, Manager, State};
-use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
-use tauri_plugin_shell::{process::CommandChild, ShellExt};
+use tauri::{AppHandle, Emitter, Listener, Manager, State};
 
 /// Check for app updates and prompt user to install
 #[cfg(desktop)]
@@ -76,19 +90,20 @@ fn show_in_dock(_app: &AppHandle) {}
 fn hide_from_dock(_app: &AppHandle) {}
 
 #[cfg(target_os = "windows")]
-fn normalize_windows_path(path: std::path::PathBuf) -> std::path::PathBuf {
+pub(crate) fn normalize_windows_path(path: std::path::PathBuf) -> std::path::PathBuf {
     let path_str = path.to_string_lossy();
     let stripped = path_str.strip_prefix(r"\\?\").unwrap_or(&path_str);
     std::path::PathBuf::from(stripped)
 }
 
 #[cfg(not(target_os = "windows"))]
-fn normalize_windows_path(path: std::path::PathBuf) -> std::path::PathBuf {
+pub(crate) fn normalize_windows_path(path: std::path::PathBuf) -> std::path::PathBuf {
     path
 }
 
 /// Show the main window and emit an event to focus the chat input
 fn show_window(app: &AppHandle) {
+    notifications::clear_pending_click(app);
     show_in_dock(app);
     if let Some(window) = app.get_webview_window("main") {
         let _ = window.unminimize();
@@ -99,35 +114,40 @@ fn show_window(app: &AppHandle) {
     }
 }
 
+/// Whether closing the main window should hide it to the tray, keeping the
+/// sidecar and wake locks alive, rather than quit
+fn close_to_tray(app: &AppHandle) -> bool {
+    app.try_state::<settings::SettingsStore>()
+        .and_then(|store| store.settings().close_to_tray)
+        .unwrap_or(true)
+}
+
+/// Hide the main window to the tray, optionally taking the app out of the
+/// dock too. Does nothing if the window is already hidden.
+fn hide_window(app: &AppHandle, remove_from_dock: bool) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    if !window.is_visible().unwrap_or(false) {
+        return;
+    }
+    let _ = window.hide();
+    if remove_from_dock {
+        hide_from_dock(app);
+    }
+}
+
 /// Toggle window visibility - show if hidden, hide to tray if visible
 fn toggle_window(app: &AppHandle) {
     if let Some(window) = app.get_webview_window("main") {
         if window.is_visible().unwrap_or(false) {
-            let _ = window.hide();
-            hide_from_dock(app);
+            hide_window(app, true);
         } else {
             show_window(app);
         }
     }
 }
 
-/// Sidecar state management
-pub struct SidecarState {
-    pub child: Mutex<Option<CommandChild>>,
-    pub host: String,
-    pub port: u16,
-}
-
-impl Default for SidecarState {
-    fn default() -> Self {
-        Self {
-            child: Mutex::new(None),
-            host: std::env::var("PIPALI_HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
-            port: std::env::var("PIPALI_PORT").unwrap_or_else(|_| "6464".to_string()).parse().unwrap_or(6464),
-        }
-    }
-}
-
 /// Get the app data directory for storing the database
 fn get_app_data_dir(app: &AppHandle) -> Result<std::path::PathBuf, String> {
     app.path()
@@ -145,12 +165,14 @@ fn get_home_dir() -> Option<std::path::PathBuf> {
     std::env::var_os("HOME").map(std::path::PathBuf::from)
 }
 
-fn get_legacy_data_dir() -> Option<std::path::PathBuf> {
+pub(crate) fn get_legacy_data_dir() -> Option<std::path::PathBuf> {
     #[cfg(target_os = "macos")]
     {
-        return get_home_dir().map(|home| {
-            home.join("Library").join("Application Support").join("pipali")
-        });
+        get_home_dir().map(|home| {
+            home.join("Library")
+                .join("Application Support")
+                .join("pipali")
+        })
     }
 
     #[cfg(target_os = "windows")]
@@ -158,7 +180,7 @@ fn get_legacy_data_dir() -> Option<std::path::PathBuf> {
         if let Some(appdata) = std::env::var_os("APPDATA") {
             return Some(std::path::PathBuf::from(appdata).join("pipali"));
         }
-        return get_home_dir().map(|home| home.join("AppData").join("Roaming").join("pipali"));
+        get_home_dir().map(|home| home.join("AppData").join("Roaming").join("pipali"))
     }
 
     #[cfg(not(any(target_os = "macos", target_os = "windows")))]
@@ -166,7 +188,7 @@ fn get_legacy_data_dir() -> Option<std::path::PathBuf> {
         if let Some(xdg_data_home) = std::env::var_os("XDG_DATA_HOME") {
             return Some(std::path::PathBuf::from(xdg_data_home).join("pipali"));
         }
-        return get_home_dir().map(|home| home.join(".local").join("share").join("pipali"));
+        get_home_dir().map(|home| home.join(".local").join("share").join("pipali"))
     }
 }
 
@@ -174,259 +196,55 @@ fn has_existing_data_dir(dir: &std::path::Path) -> bool {
     dir.join("db").exists() || dir.join("pipali.db").exists()
 }
 
-/// Get the path to the bundled server source directory
-fn get_server_resource_dir(app: &AppHandle) -> Result<std::path::PathBuf, String> {
-    app.path()
-        .resource_dir()
-        .map(|p| p.join("resources").join("server"))
-        .map_err(|e| format!("Failed to get resource dir: {}", e))
-}
-
-/// Start the sidecar process
-///
-/// This starts the Pipali server using the bundled Bun runtime.
-/// The server source code is bundled in the resources directory,
-/// and we use the bundled Bun binary to run it.
-pub fn start_sidecar(app: &AppHandle) -> Result<(), String> {
-    let state: State<SidecarState> = app.state();
-    let host = state.host.clone();
-    let port = state.port;
-
-    // Check if already running
-    if state.child.lock().unwrap().is_some() {
-        log::info!("[Sidecar] Already running");
-        return Ok(());
-    }
-
-    // Get and create the app data directory for the database
+/// Resolve the directory holding the database and settings, preferring the
+/// legacy location when it already contains data
+pub(crate) fn resolve_data_dir(app: &AppHandle) -> Result<std::path::PathBuf, String> {
     let app_data_dir = normalize_windows_path(get_app_data_dir(app)?);
-    let legacy_data_dir = get_legacy_data_dir();
-    let data_dir = legacy_data_dir
-        .as_ref()
+    let data_dir = get_legacy_data_dir()
         .filter(|dir| has_existing_data_dir(dir))
-        .cloned()
         .unwrap_or(app_data_dir);
-    let data_dir = normalize_windows_path(data_dir);
-
-    if legacy_data_dir.as_ref().is_some_and(|dir| dir == &data_dir) {
-        log::info!("[Sidecar] Using legacy data directory: {:?}", data_dir);
-    }
-
-    std::fs::create_dir_all(&data_dir)
-        .map_err(|e| format!("Failed to create app data dir: {}", e))?;
-
-    // Get the bundled server directory
-    let server_dir = normalize_windows_path(get_server_resource_dir(app)?);
-    log::info!("[Sidecar] Server directory: {:?}", server_dir);
-
-    // Verify the server entry point exists
-    // The server is bundled into a single JS file at dist/index.js
-    let entry_point = server_dir.join("dist").join("index.js");
-    if !entry_point.exists() {
-        return Err(format!(
-            "Server entry point not found: {:?}. The app bundle may be corrupted.",
-            entry_point
-        ));
-    }
-
-    log::info!("[Sidecar] Starting on {}:{}...", host, port);
-    log::info!("[Sidecar] Data directory: {:?}", data_dir);
-
-    // Use NODE_USE_SYSTEM_CA=1 to ensure Bun uses the OS certificate store for SSL verification.
-    // This handles corporate proxies, custom CAs, and system-trusted certificates properly.
-    // See: https://bun.com/blog/bun-v1.2.23
-    //
-    // Also pass PIPALI_PLATFORM_URL if set (used for connecting to remote platform instances)
-    let platform_url = std::env::var("PIPALI_PLATFORM_URL").ok();
-
-    // Build args for the server
-    // The bundled Bun will run: bun run dist/index.js --port ... --host ...
-    let mut args = vec![
-        "run".to_string(),
-        entry_point.to_string_lossy().to_string(),
-        "--port".to_string(),
-        port.to_string(),
-        "--host".to_string(),
-        host.clone(),
-    ];
-    if let Some(ref url) = platform_url {
-        log::info!("[Sidecar] Using platform URL: {}", url);
-        args.push("--platform-url".to_string());
-        args.push(url.clone());
-    }
-
-    // Get the directory containing the bundled binaries (sidecars)
-    // Tauri places sidecars next to the main executable
-    let binaries_dir = std::env::current_exe()
-        .ok()
-        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-        .unwrap_or_default();
-    let binaries_dir = normalize_windows_path(binaries_dir);
-
-    // Use the bundled Bun runtime to start the server
-    // The "bun" sidecar is registered in tauri.conf.json
-    let sidecar_command = app
-        .shell()
-        .sidecar("bun")
-        .map_err(|e| format!("Failed to create Bun sidecar command: {}", e))?
-        .args(&args)
-        .env("NODE_USE_SYSTEM_CA", "1")
-        .env("NODE_ENV", "production")
-        .env("PIPALI_DATA_DIR", data_dir.to_string_lossy().to_string())
-        // Set PIPALI_BUNDLED_RUNTIMES_DIR so the server knows where to find bundled uv/uvx
-        .env("PIPALI_BUNDLED_RUNTIMES_DIR", binaries_dir.to_string_lossy().to_string())
-        // Provide the server resources root for migrations/assets
-        .env("PIPALI_SERVER_RESOURCE_DIR", server_dir.to_string_lossy().to_string())
-        .current_dir(data_dir);
-
-    let (mut rx, child) = sidecar_command
-        .spawn()
-        .map_err(|e| format!("Failed to spawn Bun sidecar: {}", e))?;
-
-    // Store the child process
-    *state.child.lock().unwrap() = Some(child);
-
-    // Spawn a task to handle stdout/stderr
-    let app_handle = app.clone();
-    tauri::async_runtime::spawn(async move {
-        use tauri_plugin_shell::process::CommandEvent;
-        while let Some(event) = rx.recv().await {
-            match event {
-                CommandEvent::Stdout(line) => {
-                    log::info!("[Sidecar] {}", String::from_utf8_lossy(&line));
-                }
-                CommandEvent::Stderr(line) => {
-                    log::warn!("[Sidecar] {}", String::from_utf8_lossy(&line));
-                }
-                CommandEvent::Error(err) => {
-                    log::error!("[Sidecar] Error: {}", err);
-                }
-                CommandEvent::Terminated(payload) => {
-                    log::info!(
-                        "[Sidecar] Terminated with code: {:?}, signal: {:?}",
-                        payload.code,
-                        payload.signal
-                    );
-                    #[cfg(target_os = "windows")]
-                    if payload.code == Some(-1073741795) {
-                        log::error!(
-                            "[Sidecar] Bun crashed with illegal instruction. This usually indicates an unsupported CPU instruction set."
-                        );
-                    }
-                    // Clear the child state
-                    if let Some(state) = app_handle.try_state::<SidecarState>() {
-                        *state.child.lock().unwrap() = None;
-                    }
-                    break;
-                }
-                _ => {}
-            }
-        }
-    });
-
-    log::info!("[Sidecar] Process spawned, waiting for server to be ready...");
-    Ok(())
-}
-
-/// Wait for the sidecar to be ready by polling the health endpoint
-pub fn wait_for_sidecar_ready(host: &str, port: u16) -> Result<(), String> {
-    let health_url = format!("http://{}:{}/api/health", host, port);
-    let max_attempts = 50; // 10 seconds total (50 * 200ms)
-
-    // Create a ureq agent with a short timeout for health checks
-    let agent = ureq::AgentBuilder::new()
-        .timeout_connect(Duration::from_millis(500))
-        .timeout(Duration::from_secs(2))
-        .build();
-
-    for attempt in 1..=max_attempts {
-        // Use native Rust HTTP client (no console windows on Windows)
-        match agent.get(&health_url).call() {
-            Ok(response) => {
-                if response.status() == 200 {
-                    log::info!("[Sidecar] Server ready after {} attempts", attempt);
-                    return Ok(());
-                }
-            }
-            Err(_) => {}
-        }
-
-        if attempt < max_attempts {
-            std::thread::sleep(Duration::from_millis(200));
-        }
-    }
-
-    Err("Sidecar failed to become ready within timeout".to_string())
-}
-
-/// Stop the sidecar process gracefully
-pub fn stop_sidecar(app: &AppHandle) -> Result<(), String> {
-    let state: State<SidecarState> = app.state();
-    let mut child_guard = state.child.lock().unwrap();
-
-    if let Some(child) = child_guard.take() {
-        log::info!("[Sidecar] Stopping...");
-
-        let pid = child.pid();
-
-        #[cfg(unix)]
-        {
-            if let Err(e) = send_sigterm(pid) {
-                log::warn!("[Sidecar] Failed to send SIGTERM (pid={}): {}", pid, e);
-            }
-
-            let deadline = Instant::now() + Duration::from_secs(3);
-            while Instant::now() < deadline {
-                if !is_process_alive(pid) {
-                    log::info!("[Sidecar] Stopped gracefully (pid={})", pid);
-                    return Ok(());
-                }
-                std::thread::sleep(Duration::from_millis(100));
-            }
-
-            log::warn!("[Sidecar] Graceful stop timed out, forcing kill (pid={})", pid);
-        }
-
-        child
-            .kill()
-            .map_err(|e| format!("Failed to kill sidecar: {}", e))?;
-        log::info!("[Sidecar] Stopped");
-    }
-
-    Ok(())
+    Ok(normalize_windows_path(data_dir))
 }
 
-#[cfg(unix)]
-fn send_sigterm(pid: u32) -> Result<(), String> {
-    let status = std::process::Command::new("kill")
-        .args(["-TERM", &pid.to_string()])
-        .status()
-        .map_err(|e| e.to_string())?;
-
-    if status.success() {
-        Ok(())
-    } else {
-        Err(format!("kill exited with status {}", status))
-    }
-}
-
-#[cfg(unix)]
-fn is_process_alive(pid: u32) -> bool {
-    std::process::Command::new("kill")
-        .args(["-0", &pid.to_string()])
-        .status()
-        .is_ok_and(|s| s.success())
+/// Get the path to the bundled server source directory
+pub(crate) fn get_server_resource_dir(app: &AppHandle) -> Result<std::path::PathBuf, String> {
+    app.path()
+        .resource_dir()
+        .map(|p| p.join("resources").join("server"))
+        .map_err(|e| format!("Failed to get resource dir: {}", e))
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    logging::init_logging();
 
     tauri::Builder::default()
+        // Registered first so a second launch exits before any other
+        // plugin or the sidecar starts. The lock is held by the OS (a named
+        // mutex, D-Bus name or socket) and goes away if this process dies.
+        .plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
+            // When a second instance is launched, focus the existing window
+            log::info!("[App] Second instance detected, focusing existing window");
+            // Deep links in argv are passed on to the deep-link plugin by the
+            // single-instance `deep-link` feature
+            let args = argv.get(1..).unwrap_or_default();
+            // Files opened with the app are attached as if dropped on it
+            let paths = attachments::file_args(args, std::path::Path::new(&cwd));
+            if !paths.is_empty() {
+                log::info!("[App] {} file(s) from second instance", paths.len());
+                let _ = app.emit("file-dropped", serde_json::json!({ "paths": paths }));
+            }
+            show_window(app);
+        }))
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            Some(vec![autostart::LAUNCHED_AT_LOGIN_ARG]),
+        ))
         .plugin(
             tauri_plugin_global_shortcut::Builder::new()
                 .with_handler(|app, _shortcut, event| {
@@ -438,27 +256,18 @@ pub fn run() {
                 .build(),
         )
         .plugin(tauri_plugin_deep_link::init())
-        .plugin(tauri_plugin_single_instance::init(|app, argv, _cwd| {
-            // When a second instance is launched, focus the existing window
-            log::info!("[App] Second instance detected, focusing existing window");
-            // Check if the second instance was launched with a deep link URL
-            // The deep-link feature of single-instance plugin passes URLs in argv
-            for arg in argv.iter().skip(1) {
-                if arg.starts_with("pipali://") {
-                    log::info!("[App] Deep link from second instance: {}", arg);
-                    let _ = app.emit("deep-link", arg.clone());
-                }
-            }
-            show_window(app);
-        }))
         .manage(SidecarState::default())
         .manage(wake_lock::WakeLockState::default())
+        .manage(shortcut::ShortcutState::default())
+        .manage(deep_link::DeepLinkState::default())
+        .manage(notifications::NotificationState::default())
         .setup(|app| {
             // Initialize updater plugin
             #[cfg(desktop)]
             {
                 let handle = app.handle().clone();
-                app.handle().plugin(tauri_plugin_updater::Builder::new().build())?;
+                app.handle()
+                    .plugin(tauri_plugin_updater::Builder::new().build())?;
 
                 // Check for updates on startup (non-blocking)
                 tauri::async_runtime::spawn(async move {
@@ -469,23 +278,43 @@ pub fn run() {
             }
 
             let handle = app.handle().clone();
-            let state: State<SidecarState> = app.state();
-            let host = state.host.clone();
-            let port = state.port;
-
-            // Resolve the data directory (same logic as start_sidecar)
-            let app_data_dir = normalize_windows_path(get_app_data_dir(&handle)?);
-            let legacy_data_dir = get_legacy_data_dir();
-            let data_dir = legacy_data_dir
-                .as_ref()
-                .filter(|dir| has_existing_data_dir(dir))
-                .cloned()
-                .unwrap_or(app_data_dir);
-            let data_dir = normalize_windows_path(data_dir);
-
-            // Initialize wake lock state with data directory so preference persists across restarts
+            let data_dir = resolve_data_dir(&handle)?;
+
+            let settings_store = settings::SettingsStore::load(&data_dir);
+            settings_store.init(&handle);
+            app.manage(settings_store.clone());
+            logging::init_log_level(&handle, &settings_store);
+
+            // Initialize wake lock state with the settings store so preference persists across restarts
             let wake_state: State<wake_lock::WakeLockState> = app.state();
-            wake_state.init(&data_dir);
+            wake_state.init(&handle, &settings_store);
+            settings_store.watch();
+
+            // Put the main window back where it was left before it's shown
+            window_state::restore_window_state(&handle, &settings_store);
+            window_state::track_window_state(&handle, &settings_store);
+            window_state::restore_always_on_top(&handle, &settings_store);
+            window_opacity::restore_window_opacity(&handle, &settings_store);
+            if let Some(main_window) = handle.get_webview_window("main") {
+                window_state::apply_zoom(&main_window, &settings_store);
+            }
+
+            autostart::sync_launch_at_login(&handle, &settings_store);
+            // Launched at login: skip the splash and come up in the tray
+            let start_hidden = autostart::should_start_hidden(&settings_store);
+            if start_hidden {
+                log::info!("[App] Launched at login, starting in the tray");
+                if let Some(splash) = handle.get_webview_window("splashscreen") {
+                    let _ = splash.close();
+                }
+                hide_from_dock(&handle);
+            }
+
+            // Point at the sidecar configured in settings, and restart it
+            // whenever that configuration changes
+            let sidecar_state: State<SidecarState> = app.state();
+            sidecar_state.default_instance().configure(&settings_store);
+            sidecar::watch_sidecar_settings(&handle);
 
             // Show app in dock immediately
             show_in_dock(&handle);
@@ -494,24 +323,63 @@ pub fn run() {
             log::info!("[App] Splash window should be visible");
 
             // Start sidecar during setup
-            if let Err(e) = start_sidecar(&handle) {
-                log::error!("Failed to start sidecar: {}", e);
-                return Err(e.into());
+            match sidecar::start_sidecar(&handle, DEFAULT_SIDECAR) {
+                Ok(()) => {}
+                Err(sidecar::SidecarStartError::AutoStartDisabled) => {
+                    log::info!("[Sidecar] Auto-start disabled, expecting a server started by hand");
+                }
+                Err(e) => {
+                    log::error!("Failed to start sidecar: {}", e);
+                    return Err(e.into());
+                }
             }
 
+            // Named instances marked `autostart` don't hold up the splash screen
+            for name in sidecar::autostart_sidecars(&settings_store) {
+                let app_handle = handle.clone();
+                tauri::async_runtime::spawn(async move {
+                    let started = sidecar::start_sidecar(&app_handle, &name);
+                    let ready = match started {
+                        Ok(()) => sidecar::wait_for_started_sidecar(&app_handle, &name).await,
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = ready {
+                        log::error!("Sidecar {} not ready: {}", name, e);
+                        let _ = app_handle.emit(
+                            "sidecar-start-failed",
+                            serde_json::json!({ "name": name, "error": e }),
+                        );
+                    }
+                });
+            }
+
+            tauri::async_runtime::spawn(sidecar::monitor_sidecar_health(handle.clone()));
+            tauri::async_runtime::spawn(sidecar_resources::sample_sidecar_resources(
+                handle.clone(),
+            ));
+
             // Spawn async task to wait for sidecar and transition windows
             // This allows the event loop to start so windows can render
             let app_handle = handle.clone();
             tauri::async_runtime::spawn(async move {
                 // Wait for sidecar to be ready
-                if let Err(e) = wait_for_sidecar_ready(&host, port) {
+                if let Err(e) =
+                    sidecar::wait_for_started_sidecar(&app_handle, DEFAULT_SIDECAR).await
+                {
                     log::error!("Sidecar not ready: {}", e);
                     // Don't fail - the UI will show connection error
+                    let _ = app_handle.emit(
+                        "sidecar-start-failed",
+                        serde_json::json!({ "name": DEFAULT_SIDECAR, "error": e }),
+                    );
                 }
 
                 // Emit sidecar-ready event so frontend can start fetching data
                 log::info!("[App] Emitting sidecar-ready event");
                 let _ = app_handle.emit("sidecar-ready", ());
+                if start_hidden {
+                    return;
+                }
 
                 // Signal splash screen to start transformation animation
                 log::info!("[App] Server ready, triggering splash animation");
@@ -540,20 +408,50 @@ pub fn run() {
             let keep_awake_item = CheckMenuItemBuilder::with_id("keep_awake", "Keep Device Awake")
                 .checked(wake_state.is_user_enabled())
                 .build(app)?;
+            let keep_display_awake_item =
+                CheckMenuItemBuilder::with_id("keep_display_awake", "Keep Display On")
+                    .checked(wake_state.is_user_display_enabled())
+                    .build(app)?;
+            let always_on_top_item =
+                CheckMenuItemBuilder::with_id("always_on_top", "Always on Top")
+                    .checked(window_state::is_always_on_top(&settings_store))
+                    .build(app)?;
+            let restart_sidecar_item =
+                MenuItemBuilder::with_id("restart_sidecar", "Restart Server").build(app)?;
             let quit_item = MenuItemBuilder::with_id("quit", "Quit").build(app)?;
             let tray_menu = MenuBuilder::new(app)
                 .item(&show_item)
                 .separator()
                 .item(&keep_awake_item)
+                .item(&keep_display_awake_item)
+                .item(&always_on_top_item)
+                .separator()
+                .item(&restart_sidecar_item)
                 .separator()
                 .item(&quit_item)
                 .build()?;
 
+            // Keep the check marks in sync when keep-awake is changed elsewhere
+            let app_handle = app.handle().clone();
+            let (awake_item, display_item) =
+                (keep_awake_item.clone(), keep_display_awake_item.clone());
+            app.listen(wake_lock::CHANGED_EVENT, move |_| {
+                let state: State<wake_lock::WakeLockState> = app_handle.state();
+                let _ = awake_item.set_checked(state.is_user_enabled());
+                let _ = display_item.set_checked(state.is_user_display_enabled());
+            });
+            let on_top_item = always_on_top_item.clone();
+            app.listen(window_state::ALWAYS_ON_TOP_EVENT, move |event| {
+                if let Ok(enabled) = serde_json::from_str::<bool>(event.payload()) {
+                    let _ = on_top_item.set_checked(enabled);
+                }
+            });
+
             // Get the tray icon created from tauri.conf.json and set its menu
             if let Some(tray) = app.tray_by_id("main-tray") {
                 tray.set_menu(Some(tray_menu))?;
 
-                // Handle tray icon click - toggle window visibility
+                // Handle tray icon click - bring the window up
                 let app_handle = app.handle().clone();
                 tray.on_tray_icon_event(move |_tray, event| {
                     if let TrayIconEvent::Click {
@@ -562,19 +460,15 @@ pub fn run() {
                         ..
                     } = event
                     {
-                        if let Some(window) = app_handle.get_webview_window("main") {
-                            if window.is_visible().unwrap_or(false) {
-                                let _ = window.hide();
-                                hide_from_dock(&app_handle);
-                            } else {
-                                show_window(&app_handle);
-                            }
-                        }
+                        show_window(&app_handle);
                     }
                 });
 
                 // Handle tray menu item clicks
                 let app_handle = app.handle().clone();
+                let keep_awake_item = keep_awake_item.clone();
+                let keep_display_awake_item = keep_display_awake_item.clone();
+                let always_on_top_item = always_on_top_item.clone();
                 tray.on_menu_event(move |_tray, event| {
                     match event.id().as_ref() {
                         "show" => {
@@ -582,10 +476,60 @@ pub fn run() {
                         }
                         "keep_awake" => {
                             let state: State<wake_lock::WakeLockState> = app_handle.state();
-                            let is_checked = state.user_toggle();
-                            log::info!("[WakeLock] User toggled keep awake: {}", is_checked);
+                            match state.user_toggle() {
+                                Ok(is_checked) => {
+                                    log::info!(
+                                        "[WakeLock] User toggled keep awake: {}",
+                                        is_checked
+                                    );
+                                }
+                                Err(e) => {
+                                    log::error!("[WakeLock] Failed to toggle keep awake: {}", e)
+                                }
+                            }
+                            // The menu toggles its own check mark, so resync it on failure
+                            let _ = keep_awake_item.set_checked(state.is_user_enabled());
+                        }
+                        "keep_display_awake" => {
+                            let state: State<wake_lock::WakeLockState> = app_handle.state();
+                            match state.user_toggle_display() {
+                                Ok(is_checked) => {
+                                    log::info!(
+                                        "[WakeLock] User toggled keep display on: {}",
+                                        is_checked
+                                    );
+                                }
+                                Err(e) => log::error!(
+                                    "[WakeLock] Failed to toggle keep display on: {}",
+                                    e
+                                ),
+                            }
+                            let _ = keep_display_awake_item
+                                .set_checked(state.is_user_display_enabled());
+                        }
+                        "always_on_top" => {
+                            let store: State<settings::SettingsStore> = app_handle.state();
+                            if let Err(e) = window_state::toggle_always_on_top(&app_handle, &store)
+                            {
+                                log::error!("[Window] Failed to toggle always on top: {}", e);
+                            }
+                            let _ = always_on_top_item
+                                .set_checked(window_state::is_always_on_top(&store));
+                        }
+                        "restart_sidecar" => {
+                            log::info!("[Sidecar] Restart requested from tray menu");
+                            let app_handle = app_handle.clone();
+                            tauri::async_runtime::spawn(async move {
+                                if let Err(e) =
+                                    sidecar::restart_sidecar(&app_handle, DEFAULT_SIDECAR).await
+                                {
+                                    log::error!("[Sidecar] Restart from tray menu failed: {}", e);
+                                }
+                            });
                         }
                         "quit" => {
+                            // Exiting runs the same shutdown as Cmd+Q: the
+                            // sidecar is stopped and wake locks released
                             log::info!("[App] Quit requested from tray menu");
                             app_handle.exit(0);
                         }
@@ -594,26 +538,11 @@ pub fn run() {
                 });
             }
 
-            // Register global shortcut: Alt+Space
-            let shortcut: Shortcut = "Alt+Space".parse().unwrap();
-            match app.global_shortcut().register(shortcut) {
-                Ok(_) => log::info!("[App] Global shortcut Alt+Space registered"),
-                Err(e) => log::warn!("[App] Failed to register Alt+Space shortcut (may already be in use): {e}"),
-            }
+            // Register the show/hide shortcut from settings (Alt+Space by default)
+            shortcut::init_show_hide_shortcut(&handle, &settings_store);
 
-            // Handle deep links when app is already running (macOS)
-            #[cfg(any(target_os = "macos", target_os = "ios"))]
-            {
-                use tauri_plugin_deep_link::DeepLinkExt;
-                let app_handle = app.handle().clone();
-                app.deep_link().on_open_url(move |event| {
-                    for url in event.urls() {
-                        log::info!("[App] Deep link received: {}", url);
-                        let _ = app_handle.emit("deep-link", url.to_string());
-                        show_window(&app_handle);
-                    }
-                });
-            }
+            // Route pipali:// links, including one the app was launched with
+            deep_link::init_deep_links(&handle);
 
             Ok(())
         })
@@ -621,73 +550,143 @@ pub fn run() {
             commands::get_sidecar_port,
             commands::get_sidecar_host,
             commands::get_sidecar_config,
+            commands::list_sidecars,
+            commands::start_sidecar,
+            commands::stop_sidecar,
             commands::restart_sidecar,
+            commands::get_sidecar_status,
+            commands::get_sidecar_crashes,
+            commands::clear_sidecar_logs,
+            commands::get_sidecar_log_path,
+            commands::reveal_sidecar_log,
+            commands::get_sidecar_version,
+            commands::get_sidecar_resources,
+            commands::retry_sidecar,
+            commands::enable_sidecar_log_streaming,
             commands::focus_window,
-            commands::get_dropped_file_metadata,
+            commands::hide_window,
+            commands::open_window,
+            commands::set_dock_visible,
+            commands::request_user_attention,
+            commands::set_zoom,
+            commands::get_zoom,
+            commands::toggle_always_on_top,
+            commands::toggle_fullscreen,
+            commands::set_window_opacity,
+            attachments::get_dropped_file_metadata,
+            attachments::get_dropped_files_summary,
+            attachments::generate_thumbnails,
+            attachments::import_dropped_files,
+            attachments::attach_clipboard_image,
+            attachments::open_path,
+            attachments::reveal_in_file_manager,
             wake_lock::acquire_wake_lock,
-            wake_lock::release_wake_lock
+            wake_lock::release_wake_lock,
+            wake_lock::acquire_wake_lock_for,
+            wake_lock::release_wake_lock_timer,
+            wake_lock::get_wake_lock_status,
+            wake_lock::set_keep_awake_on_battery,
+            wake_lock::acquire_wake_lock_tagged,
+            wake_lock::release_wake_lock_tagged,
+            wake_lock::set_keep_awake_schedule,
+            wake_lock::sidecar_work_started,
+            wake_lock::sidecar_work_finished,
+            wake_lock::set_max_wake_lock_minutes,
+            wake_lock::set_keep_awake,
+            settings::get_setting,
+            settings::set_setting,
+            settings::get_setting_ns,
+            settings::set_setting_ns,
+            settings::set_settings,
+            settings::export_settings,
+            settings::export_settings_with_dialog,
+            export::save_file_dialog,
+            logging::set_log_level,
+            settings::import_settings,
+            settings::restore_settings_backup,
+            settings::reset_settings,
+            autostart::set_launch_at_login,
+            autostart::get_launch_at_login,
+            shortcut::set_show_hide_shortcut,
+            deep_link::take_pending_deep_links,
+            notifications::notify
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
         .run(|app_handle, event| {
             match event {
+                // Only hide main window to tray, let splashscreen close normally.
+                // With `close_to_tray` off the close goes ahead, and closing
+                // the last window quits through the shutdown below.
                 tauri::RunEvent::WindowEvent {
                     label,
                     event: tauri::WindowEvent::CloseRequested { api, .. },
                     ..
+                } if label == "main" && close_to_tray(app_handle) => {
+                    api.prevent_close();
+                    hide_window(app_handle, true);
+                    log::info!("[App] Window '{}' hidden to tray", label);
+                }
+                // macOS and Windows stop flashing on focus by themselves, but
+                // the X11 urgency hint stays set until it's cleared
+                tauri::RunEvent::WindowEvent {
+                    label,
+                    event: tauri::WindowEvent::Focused(true),
+                    ..
                 } => {
-                    // Only hide main window to tray, let splashscreen close normally
+                    if let Some(window) = app_handle.get_webview_window(&label) {
+                        let _ = window.request_user_attention(None);
+                    }
                     if label == "main" {
-                        api.prevent_close();
-                        if let Some(window) = app_handle.get_webview_window(&label) {
-                            let _ = window.hide();
-                        }
-                        hide_from_dock(app_handle);
-                        log::info!("[App] Window '{}' hidden to tray", label);
+                        notifications::notification_activated(app_handle);
                     }
                 }
                 tauri::RunEvent::WindowEvent {
                     event: tauri::WindowEvent::DragDrop(drag_event),
                     ..
-                } => {
-                    match drag_event {
-                        tauri::DragDropEvent::Enter { paths, .. } => {
-                            let _ = app_handle.emit("file-drag-enter", serde_json::json!({
+                } => match drag_event {
+                    tauri::DragDropEvent::Enter { paths, .. } => {
+                        let _ = app_handle.emit(
+                            "file-drag-enter",
+                            serde_json::json!({
                                 "count": paths.len(),
-                            }));
-                        }
-                        tauri::DragDropEvent::Drop { paths, .. } => {
-                            let path_strings: Vec<String> = paths
-                                .iter()
-                                .map(|p| p.to_string_lossy().to_string())
-                                .collect();
-                            let _ = app_handle.emit("file-dropped", serde_json::json!({
+                            }),
+                        );
+                    }
+                    tauri::DragDropEvent::Drop { paths, .. } => {
+                        let path_strings: Vec<String> = paths
+                            .iter()
+                            .map(|p| p.to_string_lossy().to_string())
+                            .collect();
+                        let _ = app_handle.emit(
+                            "file-dropped",
+                            serde_json::json!({
                                 "paths": path_strings,
-                            }));
-                        }
-                        tauri::DragDropEvent::Leave => {
-                            let _ = app_handle.emit("file-drag-leave", ());
-                        }
-                        _ => {}
+                            }),
+                        );
                     }
-                }
+                    tauri::DragDropEvent::Leave => {
+                        let _ = app_handle.emit("file-drag-leave", ());
+                    }
+                    _ => {}
+                },
                 tauri::RunEvent::ExitRequested { .. } => {
                     // Graceful shutdown on app exit (Cmd+Q, etc.)
                     log::info!("[App] Exit requested, stopping sidecar...");
-                    if let Err(e) = stop_sidecar(app_handle) {
-                        log::error!("Error stopping sidecar on exit: {}", e);
-                    }
+                    tauri::async_runtime::block_on(sidecar::stop_all_sidecars(app_handle));
                 }
                 tauri::RunEvent::Exit => {
                     // Final cleanup when app is exiting (best-effort).
                     log::info!("[App] Exiting, stopping sidecar...");
-                    if let Err(e) = stop_sidecar(app_handle) {
-                        log::error!("Error stopping sidecar on exit: {}", e);
-                    }
+                    tauri::async_runtime::block_on(sidecar::stop_all_sidecars(app_handle));
                     // Release wake lock on exit
                     if let Some(state) = app_handle.try_state::<wake_lock::WakeLockState>() {
                         state.release_all();
                     }
+                    // Write any settings still waiting on the debounce interval
+                    if let Some(store) = app_handle.try_state::<settings::SettingsStore>() {
+                        store.flush();
+                    }
                 }
                 _ => {}
             }
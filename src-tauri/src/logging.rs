@@ -0,0 +1,112 @@
+use log::LevelFilter;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Listener, Manager, State};
+
+use crate::settings::{self, SettingsChanged, SettingsStore};
+
+/// Environment variables that override the `log_level` setting, checked in
+/// order. Either takes `env_logger` filters such as `debug` or
+/// `pipali=trace,ureq=warn`.
+const LOG_ENV_VARS: [&str; 2] = ["PIPALI_LOG", "RUST_LOG"];
+
+/// Used when neither the environment nor settings pick a level
+const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// Set when the level came from the environment, which then wins over the
+/// `log_level` setting at startup
+static ENV_OVERRIDE: AtomicBool = AtomicBool::new(false);
+
+/// Parse a level name like `debug`, case-insensitively
+pub fn parse_log_level(level: &str) -> Result<LevelFilter, String> {
+    level.trim().parse().map_err(|_| {
+        format!(
+            "Unknown log level '{}', expected off, error, warn, info, debug or trace",
+            level
+        )
+    })
+}
+
+/// Start logging, filtered by `PIPALI_LOG` or `RUST_LOG` when set. Otherwise
+/// every record passes the logger's own filter and verbosity is controlled
+/// by the global max level, so it can change at runtime.
+pub fn init_logging() {
+    let env_filter = LOG_ENV_VARS.iter().find_map(|var| {
+        std::env::var(var)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+    });
+    let mut builder = env_logger::Builder::new();
+    match &env_filter {
+        Some(filter) => {
+            builder.parse_filters(filter);
+        }
+        None => {
+            builder.filter_level(LevelFilter::Trace);
+        }
+    }
+    builder.init();
+    if env_filter.is_some() {
+        ENV_OVERRIDE.store(true, Ordering::Release);
+    } else {
+        log::set_max_level(DEFAULT_LOG_LEVEL);
+    }
+}
+
+/// Apply the `log_level` setting, unless the environment already chose
+fn apply_log_level_setting(store: &SettingsStore) {
+    if ENV_OVERRIDE.load(Ordering::Acquire) {
+        return;
+    }
+    let level = match store.settings().log_level.as_deref().map(parse_log_level) {
+        Some(Ok(level)) => level,
+        Some(Err(e)) => {
+            log::warn!("[App] {}", e);
+            DEFAULT_LOG_LEVEL
+        }
+        None => DEFAULT_LOG_LEVEL,
+    };
+    if log::max_level() != level {
+        log::set_max_level(level);
+        log::info!("[App] Log level set to {}", level);
+    }
+}
+
+/// Apply the saved log level, and again whenever the setting changes
+pub fn init_log_level(app: &AppHandle, store: &SettingsStore) {
+    apply_log_level_setting(store);
+    let app_handle = app.clone();
+    app.listen(settings::SETTINGS_CHANGED_EVENT, move |event| {
+        let Ok(changed) = serde_json::from_str::<SettingsChanged>(event.payload()) else {
+            return;
+        };
+        if changed.keys.iter().any(|key| key == "log_level") {
+            apply_log_level_setting(&app_handle.state::<SettingsStore>());
+        }
+    });
+}
+
+/// Change how much the app logs, without a restart, and keep it for next
+/// launch. An unknown level is an error and leaves the level as it was.
+/// With `PIPALI_LOG`/`RUST_LOG` set, their filter still applies on top.
+#[tauri::command]
+pub fn set_log_level(store: State<'_, SettingsStore>, level: String) -> Result<(), String> {
+    let level = parse_log_level(&level)?;
+    store.update(|settings| settings.log_level = Some(level.to_string().to_lowercase()))?;
+    log::set_max_level(level);
+    log::info!("[App] Log level set to {}", level);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_levels_parse_case_insensitively() {
+        assert_eq!(parse_log_level("debug"), Ok(LevelFilter::Debug));
+        assert_eq!(parse_log_level(" WARN "), Ok(LevelFilter::Warn));
+        assert_eq!(parse_log_level("off"), Ok(LevelFilter::Off));
+        assert!(parse_log_level("loud").is_err());
+        assert!(parse_log_level("").is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::plugin::PermissionState;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::settings::SettingsStore;
+use crate::show_window;
+
+/// Emitted with a notification's `on_click` payload when it's clicked
+const CLICKED_EVENT: &str = "notification-clicked";
+
+/// How long after a notification is shown the window's next focus may still
+/// count as its click
+const CLICK_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+pub struct NotificationState {
+    /// The `on_click` payload of the last notification shown, and when
+    pending_click: Mutex<Option<(Instant, serde_json::Value)>>,
+}
+
+impl NotificationState {
+    /// Take the pending click payload, unless it has expired
+    fn take_click(&self) -> Option<serde_json::Value> {
+        self.pending_click
+            .lock()
+            .unwrap()
+            .take()
+            .filter(|(shown, _)| shown.elapsed() <= CLICK_TTL)
+            .map(|(_, payload)| payload)
+    }
+}
+
+/// Ask for permission the first time a notification is sent, where the
+/// platform needs it. Returns whether notifications may be shown.
+fn has_permission(app: &AppHandle) -> bool {
+    let notifications = app.notification();
+    let state = match notifications.permission_state() {
+        Ok(PermissionState::Prompt | PermissionState::PromptWithRationale) => {
+            notifications.request_permission()
+        }
+        state => state,
+    };
+    match state {
+        Ok(PermissionState::Granted) => true,
+        Ok(state) => {
+            log::info!("[Notify] Notification permission {}, not showing", state);
+            false
+        }
+        Err(e) => {
+            log::warn!("[Notify] Failed to check notification permission: {}", e);
+            false
+        }
+    }
+}
+
+/// Deliver the click of the last notification, if there's one pending.
+///
+/// Desktop notifications don't report clicks, but clicking one brings the
+/// app forward, so the window's next focus shortly after a notification
+/// counts as its click.
+pub fn notification_activated(app: &AppHandle) {
+    let state: State<NotificationState> = app.state();
+    let Some(payload) = state.take_click() else {
+        return;
+    };
+    let _ = app.emit(CLICKED_EVENT, payload);
+    show_window(app);
+}
+
+/// Forget the pending click, when the app is brought forward some other way
+/// (the tray, the show/hide shortcut, a deep link) so focusing it isn't
+/// taken for one
+pub fn clear_pending_click(app: &AppHandle) {
+    if let Some(state) = app.try_state::<NotificationState>() {
+        state.pending_click.lock().unwrap().take();
+    }
+}
+
+/// Show a native notification, e.g. when a task finishes in the background.
+/// Returns false without showing anything when `notifications_enabled` is
+/// off or permission was denied.
+///
+/// `on_click` is delivered as `notification-clicked` on a best-effort basis:
+/// desktop notifications don't report clicks, so it fires when the window is
+/// next focused within 30s, unless it was brought up by the app itself. The
+/// user switching to the app by hand in that time also counts as a click.
+#[tauri::command]
+pub fn notify(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    state: State<'_, NotificationState>,
+    title: String,
+    body: String,
+    on_click: Option<serde_json::Value>,
+) -> Result<bool, String> {
+    if !store.settings().notifications_enabled.unwrap_or(true) || !has_permission(&app) {
+        return Ok(false);
+    }
+    app.notification()
+        .builder()
+        .title(title)
+        .body(body)
+        .show()
+        .map_err(|e| format!("Failed to show notification: {}", e))?;
+
+    let focused = app
+        .get_webview_window("main")
+        .is_some_and(|window| window.is_focused().unwrap_or(false));
+    // Nothing to return to if the app is already in front
+    if !focused {
+        *state.pending_click.lock().unwrap() = on_click.map(|payload| (Instant::now(), payload));
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_click_expires() {
+        let state = NotificationState::default();
+        *state.pending_click.lock().unwrap() = Some((Instant::now(), serde_json::json!("chat")));
+        assert_eq!(state.take_click(), Some(serde_json::json!("chat")));
+        assert_eq!(state.take_click(), None);
+
+        let shown = Instant::now() - CLICK_TTL - Duration::from_secs(1);
+        *state.pending_click.lock().unwrap() = Some((shown, serde_json::json!("chat")));
+        assert_eq!(state.take_click(), None);
+    }
+}
@@ -0,0 +1,1668 @@
+use notify::Watcher;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use tauri_plugin_global_shortcut::Shortcut;
+
+use crate::export::{save_with_dialog, write_file_atomically};
+use crate::logging::parse_log_level;
+use crate::shortcut::SHOW_HIDE_SHORTCUT_KEY;
+use crate::sidecar_priority::SidecarPriority;
+use crate::wake_lock::{
+    ScheduleWindow, WakeLockSettings, WakeLockState, DEFAULT_MAX_WAKE_LOCK_MINUTES,
+};
+use crate::window_state::WindowState;
+
+pub const SETTINGS_FILE: &str = "settings.json";
+const SETTINGS_TMP_FILE: &str = "settings.json.tmp";
+
+pub const SETTINGS_CHANGED_EVENT: &str = "settings-changed";
+
+/// Wait for the settings file to be quiet this long before reloading, since
+/// editors often write several times per save
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Receives a notification after each successful settings mutation
+type ChangeSink = Box<dyn Fn(&SettingsChanged) + Send + Sync>;
+
+/// Payload of the `settings-changed` event. `key` and `value` are only set
+/// when a single preference changed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsChanged {
+    pub keys: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+}
+
+/// How many previous versions of the settings file to keep
+const SETTINGS_BACKUPS_KEY: &str = "settings_backups";
+const DEFAULT_SETTINGS_BACKUPS: u64 = 3;
+/// How long to coalesce rapid changes before writing them. Zero writes immediately.
+const SETTINGS_DEBOUNCE_MS_KEY: &str = "settings_debounce_ms";
+const DEFAULT_SETTINGS_DEBOUNCE_MS: u64 = 500;
+
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+/// Version of the settings layout written by this build
+const CURRENT_SCHEMA_VERSION: u64 = 2;
+
+type Migration = fn(&mut serde_json::Map<String, serde_json::Value>);
+
+/// Ordered migration steps; entry `n` upgrades schema version `n` to `n + 1`
+const MIGRATIONS: [Migration; CURRENT_SCHEMA_VERSION as usize] =
+    [migrate_v0_to_v1, migrate_v1_to_v2];
+
+/// Rename the original `awake` flag to `keep_awake`
+fn migrate_v0_to_v1(settings: &mut serde_json::Map<String, serde_json::Value>) {
+    if let Some(awake) = settings.remove("awake") {
+        settings.entry("keep_awake").or_insert(awake);
+    }
+}
+
+/// Move the wake lock preferences from the top level into the `wake_lock` namespace
+fn migrate_v1_to_v2(settings: &mut serde_json::Map<String, serde_json::Value>) {
+    const WAKE_LOCK_KEYS: [&str; 6] = [
+        "keep_awake",
+        "keep_display_awake",
+        "keep_awake_on_battery",
+        "keep_awake_schedule",
+        "max_wake_lock_minutes",
+        "total_awake_secs",
+    ];
+    let mut namespace = match settings.remove("wake_lock") {
+        Some(serde_json::Value::Object(namespace)) => namespace,
+        _ => serde_json::Map::new(),
+    };
+    for key in WAKE_LOCK_KEYS {
+        if let Some(value) = settings.remove(key) {
+            namespace.entry(key).or_insert(value);
+        }
+    }
+    if !namespace.is_empty() {
+        settings.insert(
+            "wake_lock".to_string(),
+            serde_json::Value::Object(namespace),
+        );
+    }
+}
+
+fn schema_version(settings: &serde_json::Value) -> u64 {
+    settings
+        .get(SCHEMA_VERSION_KEY)
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or(0)
+}
+
+/// Apply every migration step between the stored schema version and the current one
+pub fn migrate_settings(mut value: serde_json::Value) -> serde_json::Value {
+    let version = schema_version(&value);
+    let Some(settings) = value.as_object_mut() else {
+        return value;
+    };
+    for migrate in MIGRATIONS.iter().skip(version as usize) {
+        migrate(settings);
+    }
+    settings.insert(
+        SCHEMA_VERSION_KEY.to_string(),
+        serde_json::json!(version.max(CURRENT_SCHEMA_VERSION)),
+    );
+    value
+}
+
+/// Parse a settings file, returning `None` unless it holds a JSON object
+fn parse_settings_file(path: &Path) -> Option<serde_json::Value> {
+    parse_settings(&std::fs::read(path).ok()?)
+}
+
+/// Parse settings file contents, returning `None` unless they are a JSON object
+fn parse_settings(contents: &[u8]) -> Option<serde_json::Value> {
+    serde_json::from_slice::<serde_json::Value>(contents)
+        .ok()
+        .filter(serde_json::Value::is_object)
+}
+
+/// Move a corrupt settings file aside as `settings.corrupt.<timestamp>.json`
+/// so the user can recover it by hand, then restore the most recent backup
+/// that parses. Returns the restored settings, if any backup was usable.
+fn recover_corrupt_settings(data_dir: &Path) -> Option<serde_json::Value> {
+    let corrupt_path = data_dir.join(format!(
+        "settings.corrupt.{}.json",
+        chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ")
+    ));
+    match std::fs::rename(data_dir.join(SETTINGS_FILE), &corrupt_path) {
+        Ok(()) => log::error!(
+            "[Settings] {} is corrupt, moved it to {}",
+            SETTINGS_FILE,
+            corrupt_path.display()
+        ),
+        Err(e) => log::error!(
+            "[Settings] {} is corrupt and couldn't be moved: {}",
+            SETTINGS_FILE,
+            e
+        ),
+    }
+
+    let restored = (1..)
+        .map(|index| (index, backup_path(data_dir, index)))
+        .take_while(|(_, path)| path.exists())
+        .find_map(|(index, path)| parse_settings_file(&path).map(|backup| (index, backup)));
+    let Some((index, backup)) = restored else {
+        log::error!("[Settings] No usable settings backup, starting from defaults");
+        return None;
+    };
+    log::error!("[Settings] Restored settings from backup {}", index);
+    if let Err(e) = write_settings(data_dir, &backup) {
+        log::warn!("[Settings] Failed to save restored settings: {}", e);
+    }
+    Some(backup)
+}
+
+/// Settings used when there is no file to load them from
+fn empty_settings() -> serde_json::Value {
+    serde_json::json!({ SCHEMA_VERSION_KEY: CURRENT_SCHEMA_VERSION })
+}
+
+/// Read the settings file from disk, falling back to an empty object if it
+/// is missing. A corrupt file is set aside and replaced by the newest usable
+/// backup, if any. Fails, leaving the file alone, if it exists but can't be read.
+///
+/// Files from an older schema are migrated and written back. Files from a
+/// newer schema are returned as-is and treated as read-only.
+fn load_settings(data_dir: &Path) -> Result<serde_json::Value, String> {
+    let path = data_dir.join(SETTINGS_FILE);
+    if !path.exists() {
+        return Ok(empty_settings());
+    }
+    let contents =
+        std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let Some(settings) = parse_settings(&contents).or_else(|| recover_corrupt_settings(data_dir))
+    else {
+        return Ok(empty_settings());
+    };
+    let version = schema_version(&settings);
+    if version > CURRENT_SCHEMA_VERSION {
+        log::warn!(
+            "[Settings] Settings schema v{} is newer than supported v{}, loading read-only",
+            version,
+            CURRENT_SCHEMA_VERSION
+        );
+        return Ok(settings);
+    }
+    if version == CURRENT_SCHEMA_VERSION {
+        return Ok(settings);
+    }
+
+    let migrated = migrate_settings(settings);
+    match write_settings(data_dir, &migrated) {
+        Ok(_) => log::info!(
+            "[Settings] Migrated settings from schema v{} to v{}",
+            version,
+            CURRENT_SCHEMA_VERSION
+        ),
+        Err(e) => log::warn!("[Settings] Failed to save migrated settings: {}", e),
+    }
+    Ok(migrated)
+}
+
+fn backup_path(data_dir: &Path, index: u64) -> PathBuf {
+    data_dir.join(format!("{}.{}", SETTINGS_FILE, index))
+}
+
+/// Shift `settings.json.1`..`settings.json.N` up by one, dropping the oldest,
+/// and link the current file in as `settings.json.1`. Failures are logged but
+/// never block the write that follows.
+fn rotate_backups(data_dir: &Path, backups: u64) {
+    if backups == 0 {
+        return;
+    }
+    let current = data_dir.join(SETTINGS_FILE);
+    if !current.exists() {
+        return;
+    }
+    for index in (1..backups).rev() {
+        let from = backup_path(data_dir, index);
+        if from.exists() {
+            if let Err(e) = std::fs::rename(&from, backup_path(data_dir, index + 1)) {
+                log::warn!("[Settings] Failed to rotate {}: {}", from.display(), e);
+            }
+        }
+    }
+    // Link rather than rename so the settings file never goes missing
+    let newest = backup_path(data_dir, 1);
+    let _ = std::fs::remove_file(&newest);
+    if let Err(e) = std::fs::hard_link(&current, &newest) {
+        log::warn!("[Settings] Failed to back up settings: {}", e);
+    }
+}
+
+/// Write the settings file atomically.
+///
+/// The value is serialized into a sibling temp file which is then renamed
+/// over the target, so a crash mid-write never leaves a truncated file behind.
+/// Returns the hash of the written contents.
+pub fn write_settings(data_dir: &Path, value: &serde_json::Value) -> Result<u64, String> {
+    std::fs::create_dir_all(data_dir)
+        .map_err(|e| format!("Failed to create {}: {}", data_dir.display(), e))?;
+    let contents = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    let hash = content_hash(&contents);
+    let tmp_path = data_dir.join(SETTINGS_TMP_FILE);
+    std::fs::write(&tmp_path, contents)
+        .map_err(|e| format!("Failed to write {}: {}", tmp_path.display(), e))?;
+    let backups = value
+        .get(SETTINGS_BACKUPS_KEY)
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or(DEFAULT_SETTINGS_BACKUPS);
+    rotate_backups(data_dir, backups);
+    std::fs::rename(&tmp_path, data_dir.join(SETTINGS_FILE)).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp_path);
+        format!("Failed to replace {}: {}", SETTINGS_FILE, e)
+    })?;
+    Ok(hash)
+}
+
+fn content_hash(contents: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hash of the settings file currently on disk, if it exists
+fn file_hash(data_dir: &Path) -> Option<u64> {
+    std::fs::read_to_string(data_dir.join(SETTINGS_FILE))
+        .ok()
+        .map(|contents| content_hash(&contents))
+}
+
+/// Recursively merge `patch` into `target`. Nested objects are merged key
+/// by key, `null` deletes a key, and anything else (including arrays) is
+/// replaced wholesale.
+pub fn deep_merge(target: &mut serde_json::Value, patch: serde_json::Value) {
+    let serde_json::Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = serde_json::Value::Object(serde_json::Map::new());
+    }
+    let serde_json::Value::Object(target) = target else {
+        unreachable!("target was just made an object");
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(&key);
+        } else {
+            deep_merge(target.entry(key).or_insert(serde_json::Value::Null), value);
+        }
+    }
+}
+
+/// Deep-merge `patch` into the top-level settings fields
+fn merge_fields(
+    fields: &mut serde_json::Map<String, serde_json::Value>,
+    patch: serde_json::Map<String, serde_json::Value>,
+) {
+    let mut merged = serde_json::Value::Object(std::mem::take(fields));
+    deep_merge(&mut merged, serde_json::Value::Object(patch));
+    if let serde_json::Value::Object(merged) = merged {
+        *fields = merged;
+    }
+}
+
+/// Settings written by `reset_settings`
+fn default_settings() -> Settings {
+    Settings {
+        schema_version: CURRENT_SCHEMA_VERSION,
+        wake_lock: WakeLockSettings {
+            max_wake_lock_minutes: Some(DEFAULT_MAX_WAKE_LOCK_MINUTES),
+            ..WakeLockSettings::default()
+        },
+        settings_backups: Some(DEFAULT_SETTINGS_BACKUPS),
+        settings_debounce_ms: Some(DEFAULT_SETTINGS_DEBOUNCE_MS),
+        ..Settings::default()
+    }
+}
+
+/// Keys whose values differ between `before` and `after`, sorted
+fn changed_keys(
+    before: &serde_json::Map<String, serde_json::Value>,
+    after: &serde_json::Map<String, serde_json::Value>,
+) -> Vec<String> {
+    let mut keys: Vec<String> = before
+        .keys()
+        .chain(after.keys())
+        .filter(|k| *k != SCHEMA_VERSION_KEY && before.get(*k) != after.get(*k))
+        .cloned()
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// Replace `fields` with `new`, returning the keys whose values differ
+fn replace_fields(
+    fields: &mut serde_json::Map<String, serde_json::Value>,
+    new: serde_json::Map<String, serde_json::Value>,
+) -> Vec<String> {
+    let keys = changed_keys(fields, &new);
+    *fields = new;
+    keys
+}
+
+type TypeCheck = fn(&serde_json::Value) -> bool;
+
+/// Expected types of the settings this build knows about, by dotted path
+const KNOWN_KEYS: &[(&str, TypeCheck, &str)] = &[
+    ("wake_lock", serde_json::Value::is_object, "an object"),
+    (
+        "wake_lock.keep_awake",
+        serde_json::Value::is_boolean,
+        "a boolean",
+    ),
+    (
+        "wake_lock.keep_display_awake",
+        serde_json::Value::is_boolean,
+        "a boolean",
+    ),
+    (
+        "wake_lock.keep_awake_on_battery",
+        serde_json::Value::is_boolean,
+        "a boolean",
+    ),
+    (
+        "wake_lock.keep_awake_schedule",
+        is_schedule,
+        "a list of schedule windows",
+    ),
+    (
+        "wake_lock.max_wake_lock_minutes",
+        serde_json::Value::is_u64,
+        "a non-negative integer",
+    ),
+    (
+        "wake_lock.total_awake_secs",
+        serde_json::Value::is_u64,
+        "a non-negative integer",
+    ),
+    (
+        "window_state",
+        is_window_state,
+        "an object with x, y, width and height",
+    ),
+    ("close_to_tray", serde_json::Value::is_boolean, "a boolean"),
+    ("always_on_top", serde_json::Value::is_boolean, "a boolean"),
+    ("zoom", serde_json::Value::is_number, "a number"),
+    ("window_opacity", serde_json::Value::is_number, "a number"),
+    (
+        "launch_at_login",
+        serde_json::Value::is_boolean,
+        "a boolean",
+    ),
+    (
+        "start_minimized",
+        serde_json::Value::is_boolean,
+        "a boolean",
+    ),
+    (
+        "notifications_enabled",
+        serde_json::Value::is_boolean,
+        "a boolean",
+    ),
+    (
+        "log_level",
+        is_log_level,
+        "one of off, error, warn, info, debug or trace",
+    ),
+    (
+        SHOW_HIDE_SHORTCUT_KEY,
+        is_shortcut,
+        "a key combo like \"Alt+Space\"",
+    ),
+    ("sidecar", serde_json::Value::is_object, "an object"),
+    (
+        "sidecar.max_restart_attempts",
+        serde_json::Value::is_u64,
+        "a non-negative integer",
+    ),
+    (
+        "sidecar.restart_window_secs",
+        serde_json::Value::is_u64,
+        "a non-negative integer",
+    ),
+    (
+        "sidecar.stable_secs",
+        serde_json::Value::is_u64,
+        "a non-negative integer",
+    ),
+    ("sidecar.host", serde_json::Value::is_string, "a string"),
+    (
+        "sidecar.autostart",
+        serde_json::Value::is_boolean,
+        "a boolean",
+    ),
+    (
+        "sidecar.binary_path",
+        serde_json::Value::is_string,
+        "a string",
+    ),
+    ("sidecar.args", is_string_list, "a list of strings"),
+    (
+        "sidecar.http_proxy",
+        serde_json::Value::is_string,
+        "a string",
+    ),
+    (
+        "sidecar.https_proxy",
+        serde_json::Value::is_string,
+        "a string",
+    ),
+    ("sidecar.no_proxy", serde_json::Value::is_string, "a string"),
+    ("sidecar.env", is_env_map, "an object of strings"),
+    (
+        "sidecar.instances",
+        serde_json::Value::is_object,
+        "an object",
+    ),
+    (
+        "sidecar.shutdown_grace_ms",
+        serde_json::Value::is_u64,
+        "a non-negative integer",
+    ),
+    (
+        "sidecar.restart_delay_ms",
+        serde_json::Value::is_u64,
+        "a non-negative integer",
+    ),
+    (
+        "sidecar.startup_timeout_secs",
+        serde_json::Value::is_u64,
+        "a non-negative integer",
+    ),
+    (
+        "sidecar.health_interval_secs",
+        serde_json::Value::is_u64,
+        "a non-negative integer",
+    ),
+    (
+        "sidecar.health_failure_threshold",
+        serde_json::Value::is_u64,
+        "a non-negative integer",
+    ),
+    (
+        "sidecar.restart_when_unhealthy",
+        serde_json::Value::is_boolean,
+        "a boolean",
+    ),
+    ("sidecar.port", is_port, "a port number"),
+    ("sidecar.bind_address", is_ip_addr, "an IP address"),
+    (
+        "sidecar.priority",
+        is_sidecar_priority,
+        "\"low\", \"normal\" or \"high\"",
+    ),
+    (
+        SETTINGS_BACKUPS_KEY,
+        serde_json::Value::is_u64,
+        "a non-negative integer",
+    ),
+    (
+        SETTINGS_DEBOUNCE_MS_KEY,
+        serde_json::Value::is_u64,
+        "a non-negative integer",
+    ),
+    (
+        SCHEMA_VERSION_KEY,
+        serde_json::Value::is_u64,
+        "a non-negative integer",
+    ),
+];
+
+fn is_env_map(value: &serde_json::Value) -> bool {
+    value
+        .as_object()
+        .is_some_and(|env| env.values().all(serde_json::Value::is_string))
+}
+
+fn is_string_list(value: &serde_json::Value) -> bool {
+    value
+        .as_array()
+        .is_some_and(|items| items.iter().all(serde_json::Value::is_string))
+}
+
+fn is_port(value: &serde_json::Value) -> bool {
+    value
+        .as_u64()
+        .is_some_and(|port| u16::try_from(port).is_ok())
+}
+
+fn is_ip_addr(value: &serde_json::Value) -> bool {
+    value
+        .as_str()
+        .is_some_and(|addr| addr.parse::<std::net::IpAddr>().is_ok())
+}
+
+fn is_sidecar_priority(value: &serde_json::Value) -> bool {
+    SidecarPriority::deserialize(value).is_ok()
+}
+
+fn is_log_level(value: &serde_json::Value) -> bool {
+    value
+        .as_str()
+        .is_some_and(|level| parse_log_level(level).is_ok())
+}
+
+fn is_shortcut(value: &serde_json::Value) -> bool {
+    value
+        .as_str()
+        .is_some_and(|combo| combo.parse::<Shortcut>().is_ok())
+}
+
+fn is_window_state(value: &serde_json::Value) -> bool {
+    WindowState::deserialize(value).is_ok()
+}
+
+fn is_schedule(value: &serde_json::Value) -> bool {
+    Vec::<ScheduleWindow>::deserialize(value).is_ok()
+}
+
+/// Known keys in `fields` whose values have the wrong type, along with the
+/// expected type
+fn invalid_keys(settings: &serde_json::Value) -> Vec<(&'static str, &'static str)> {
+    KNOWN_KEYS
+        .iter()
+        .filter(|(key, is_valid, _)| {
+            settings
+                .pointer(&json_pointer(key))
+                .is_some_and(|v| !is_valid(v))
+        })
+        .map(|(key, _, expected)| (*key, *expected))
+        .collect()
+}
+
+/// Convert a dotted settings path like `wake_lock.keep_awake` to a JSON pointer
+fn json_pointer(path: &str) -> String {
+    format!("/{}", path.replace('.', "/"))
+}
+
+/// Check that `settings` is an object and that every known key has the
+/// expected type. The error lists each offending key.
+fn validate_settings(settings: &serde_json::Value) -> Result<(), String> {
+    if !settings.is_object() {
+        return Err("Settings must be a JSON object".to_string());
+    }
+    let invalid: Vec<String> = invalid_keys(settings)
+        .into_iter()
+        .map(|(key, expected)| format!("{} (expected {})", key, expected))
+        .collect();
+    if invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Invalid settings: {}", invalid.join(", ")))
+    }
+}
+
+/// Typed view of `settings.json`. Keys this build doesn't know about are
+/// kept in `extra` so they survive a round trip.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub schema_version: u64,
+    pub wake_lock: WakeLockSettings,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_state: Option<WindowState>,
+    /// Hide the main window to the tray on close instead of quitting.
+    /// Defaults to on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub close_to_tray: Option<bool>,
+    /// Keep the main window above other windows, except while fullscreen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub always_on_top: Option<bool>,
+    /// Webview zoom factor, clamped to 0.5-3.0 when applied
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zoom: Option<f64>,
+    /// Main window opacity, clamped to 0.2-1.0 when applied
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_opacity: Option<f64>,
+    /// Start the app at login. Kept in sync with the OS login item.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub launch_at_login: Option<bool>,
+    /// Stay in the tray when launched at login. Defaults to on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_minimized: Option<bool>,
+    /// Allow native notifications from `notify`. Defaults to on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notifications_enabled: Option<bool>,
+    /// How much the app logs, e.g. `debug`. `PIPALI_LOG` or `RUST_LOG` win
+    /// when set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+    /// Key combo that shows and hides the main window from anywhere
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub show_hide_shortcut: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settings_backups: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settings_debounce_ms: Option<u64>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Settings {
+    /// Parse settings, failing with the list of offending keys if any known
+    /// key has the wrong type
+    fn from_value(value: serde_json::Value) -> Result<Self, String> {
+        validate_settings(&value)?;
+        serde_json::from_value(value).map_err(|e| format!("Invalid settings: {}", e))
+    }
+
+    /// Parse settings, dropping any known keys with the wrong type
+    fn from_value_lossy(mut value: serde_json::Value) -> Self {
+        for (key, expected) in invalid_keys(&value) {
+            log::warn!("[Settings] Ignoring {}, expected {}", key, expected);
+            let (parent, name) = key.rsplit_once('.').unwrap_or(("", key));
+            let parent = if parent.is_empty() {
+                Some(&mut value)
+            } else {
+                value.pointer_mut(&json_pointer(parent))
+            };
+            if let Some(parent) = parent.and_then(serde_json::Value::as_object_mut) {
+                parent.remove(name);
+            }
+        }
+        serde_json::from_value(value).unwrap_or_else(|e| {
+            log::warn!("[Settings] Ignoring unreadable settings: {}", e);
+            Self::default()
+        })
+    }
+
+    fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("settings always serialize")
+    }
+
+    fn into_fields(self) -> serde_json::Map<String, serde_json::Value> {
+        match self.to_value() {
+            serde_json::Value::Object(fields) => fields,
+            _ => unreachable!("settings serialize to an object"),
+        }
+    }
+}
+
+/// Parsed settings, cached in memory.
+///
+/// Loaded once at startup so reads never touch the disk. Every mutation is
+/// written atomically first and only then applied to the cache.
+#[derive(Clone)]
+pub struct SettingsStore {
+    inner: Arc<StoreInner>,
+}
+
+struct StoreInner {
+    data_dir: PathBuf,
+    cache: RwLock<Settings>,
+    /// Serializes every read-modify-write of the settings file
+    write_lock: Mutex<()>,
+    /// Whether the cache holds changes not yet written to disk
+    dirty: AtomicBool,
+    flush_scheduled: AtomicBool,
+    /// Hash of the contents we last read or wrote, to tell our own writes
+    /// apart from external edits
+    disk_hash: Mutex<Option<u64>>,
+    change_sink: OnceLock<ChangeSink>,
+    watcher: Mutex<Option<notify::RecommendedWatcher>>,
+    /// Set while the settings file exists but couldn't be read, so the
+    /// defaults used in its place are never written over it
+    read_only: AtomicBool,
+}
+
+impl SettingsStore {
+    /// Load the settings file from `data_dir`, migrating it if needed
+    pub fn load(data_dir: &Path) -> Self {
+        let (settings, read_only) = match load_settings(data_dir) {
+            Ok(settings) => (settings, false),
+            Err(e) => {
+                log::error!("[Settings] {}, using defaults without saving changes", e);
+                (empty_settings(), true)
+            }
+        };
+        let settings = Settings::from_value_lossy(settings);
+        Self {
+            inner: Arc::new(StoreInner {
+                data_dir: data_dir.to_path_buf(),
+                cache: RwLock::new(settings),
+                write_lock: Mutex::new(()),
+                dirty: AtomicBool::new(false),
+                flush_scheduled: AtomicBool::new(false),
+                disk_hash: Mutex::new(file_hash(data_dir)),
+                change_sink: OnceLock::new(),
+                watcher: Mutex::new(None),
+                read_only: AtomicBool::new(read_only),
+            }),
+        }
+    }
+
+    /// Watch the settings file for external edits, reloading the cache and
+    /// notifying `settings-changed` listeners when it changes
+    pub fn watch(&self) {
+        let (tx, rx) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let touches_settings = event.is_ok_and(|event| {
+                event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name().is_some_and(|name| name == SETTINGS_FILE))
+            });
+            if touches_settings {
+                let _ = tx.send(());
+            }
+        });
+        // Watch the directory rather than the file, since atomic writes
+        // replace the file and would end a watch on it
+        let watcher = watcher.and_then(|mut watcher| {
+            watcher
+                .watch(&self.inner.data_dir, notify::RecursiveMode::NonRecursive)
+                .map(|()| watcher)
+        });
+        match watcher {
+            Ok(watcher) => *self.inner.watcher.lock().unwrap() = Some(watcher),
+            Err(e) => {
+                log::warn!("[Settings] Failed to watch settings file: {}", e);
+                return;
+            }
+        }
+
+        let store = self.clone();
+        let spawned = std::thread::Builder::new()
+            .name("settings-watcher".into())
+            .spawn(move || {
+                while rx.recv().is_ok() {
+                    while rx.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
+                    if let Some(keys) = store.reload() {
+                        log::info!(
+                            "[Settings] Reloaded {} externally edited preferences",
+                            keys.len()
+                        );
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            log::warn!("[Settings] Failed to start settings watcher: {}", e);
+        }
+    }
+
+    /// Reload the cache from disk after an external edit. Returns the keys
+    /// that changed, or `None` if the file is unchanged since our last read
+    /// or write, or can't be parsed.
+    fn reload(&self) -> Option<Vec<String>> {
+        let lock = self.inner.write_lock.lock().unwrap();
+        let contents = std::fs::read_to_string(self.inner.data_dir.join(SETTINGS_FILE)).ok()?;
+        let hash = content_hash(&contents);
+        if *self.inner.disk_hash.lock().unwrap() == Some(hash) {
+            return None;
+        }
+        *self.inner.disk_hash.lock().unwrap() = Some(hash);
+        let edited = serde_json::from_str::<serde_json::Value>(&contents)
+            .ok()
+            .filter(serde_json::Value::is_object)
+            .map(migrate_settings);
+        let Some(edited) = edited else {
+            log::warn!("[Settings] Ignoring external edit that isn't a JSON object");
+            return None;
+        };
+        let edited = Settings::from_value_lossy(edited);
+        if self.inner.read_only.swap(false, Ordering::AcqRel) {
+            log::info!(
+                "[Settings] {} is readable again, saving changes",
+                SETTINGS_FILE
+            );
+        }
+        if self.inner.dirty.swap(false, Ordering::AcqRel) {
+            log::warn!("[Settings] External edit replaced unsaved changes");
+        }
+        let keys = {
+            let mut cache = self.inner.cache.write().unwrap();
+            let mut fields = cache.clone().into_fields();
+            let keys = replace_fields(&mut fields, edited.clone().into_fields());
+            *cache = edited;
+            keys
+        };
+        drop(lock);
+        self.notify_keys(keys.clone());
+        Some(keys)
+    }
+
+    /// Emit `settings-changed` to every window whenever settings are mutated
+    pub fn init(&self, app: &AppHandle) {
+        let emitter = app.clone();
+        let _ = self.inner.change_sink.set(Box::new(move |change| {
+            let _ = emitter.emit(SETTINGS_CHANGED_EVENT, change);
+        }));
+    }
+
+    /// Read a single key from the cache
+    pub fn get(&self, key: &str) -> Option<serde_json::Value> {
+        self.snapshot().get_mut(key).map(serde_json::Value::take)
+    }
+
+    /// Read `key` from the `module` namespace
+    pub fn get_ns(&self, module: &str, key: &str) -> Option<serde_json::Value> {
+        self.get(module)?.get_mut(key).map(serde_json::Value::take)
+    }
+
+    /// Set `key` in the `module` namespace, leaving the module's other keys alone
+    pub fn set_ns(&self, module: &str, key: &str, value: serde_json::Value) -> Result<(), String> {
+        let mut patch = serde_json::Map::new();
+        patch.insert(key.to_string(), value);
+        self.set(module, serde_json::Value::Object(patch))
+    }
+
+    /// A copy of the cached settings
+    pub fn settings(&self) -> Settings {
+        self.inner.cache.read().unwrap().clone()
+    }
+
+    /// A copy of every cached setting as JSON
+    pub fn snapshot(&self) -> serde_json::Value {
+        self.inner.cache.read().unwrap().to_value()
+    }
+
+    /// Apply a typed change. The cache is updated immediately and the write
+    /// is debounced.
+    pub fn update(&self, update: impl FnOnce(&mut Settings)) -> Result<(), String> {
+        let (before, after) = self.modify(true, |fields| {
+            let before = fields.clone();
+            // The cache always holds valid settings, so nothing is dropped here
+            let mut settings =
+                Settings::from_value_lossy(serde_json::Value::Object(fields.clone()));
+            update(&mut settings);
+            *fields = settings.into_fields();
+            (before, fields.clone())
+        })?;
+        let mut keys = changed_keys(&before, &after);
+        if keys.len() == 1 {
+            let key = keys.remove(0);
+            self.notify(SettingsChanged {
+                keys: vec![key.clone()],
+                value: after.get(&key).cloned(),
+                key: Some(key),
+            });
+        } else if !keys.is_empty() {
+            self.notify_keys(keys);
+        }
+        Ok(())
+    }
+
+    /// Set a single key, preserving all other fields. The cache is updated
+    /// immediately and the write is debounced.
+    ///
+    /// An object value is deep-merged into the existing one, so a nested
+    /// patch leaves sibling fields alone. `null` deletes the key.
+    pub fn set(&self, key: &str, value: serde_json::Value) -> Result<(), String> {
+        let mut patch = serde_json::Map::new();
+        patch.insert(key.to_string(), value);
+        let value = self.modify(true, |fields| {
+            merge_fields(fields, patch);
+            fields.get(key).cloned()
+        })?;
+        self.notify(SettingsChanged {
+            keys: vec![key.to_string()],
+            key: Some(key.to_string()),
+            value: Some(value.unwrap_or(serde_json::Value::Null)),
+        });
+        Ok(())
+    }
+
+    /// Deep-merge several keys in one write, emitting a single change notification
+    pub fn set_many(
+        &self,
+        changes: serde_json::Map<String, serde_json::Value>,
+    ) -> Result<(), String> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let keys = changes.keys().cloned().collect();
+        self.modify(true, |fields| merge_fields(fields, changes))?;
+        self.notify_keys(keys);
+        Ok(())
+    }
+
+    /// Import settings from `src_path`, either deep-merging into or replacing
+    /// the current settings. Nothing is written unless the whole file validates.
+    /// Returns the keys that changed.
+    pub fn import_from(&self, src_path: &Path, merge: bool) -> Result<Vec<String>, String> {
+        let contents = std::fs::read_to_string(src_path)
+            .map_err(|e| format!("Failed to read {}: {}", src_path.display(), e))?;
+        let imported: serde_json::Value = serde_json::from_str(&contents)
+            .map_err(|e| format!("{} is not valid JSON: {}", src_path.display(), e))?;
+        let version = schema_version(&imported);
+        if version > CURRENT_SCHEMA_VERSION {
+            return Err(format!(
+                "Settings schema v{} is newer than supported v{}",
+                version, CURRENT_SCHEMA_VERSION
+            ));
+        }
+        let imported = migrate_settings(imported);
+        validate_settings(&imported)?;
+        let serde_json::Value::Object(imported) = imported else {
+            unreachable!("validated settings are an object");
+        };
+
+        let keys = self.modify(false, |fields| {
+            let mut keys: Vec<String> = imported.keys().cloned().collect();
+            if merge {
+                merge_fields(fields, imported);
+            } else {
+                keys.extend(
+                    fields
+                        .keys()
+                        .filter(|k| !imported.contains_key(*k))
+                        .cloned(),
+                );
+                *fields = imported;
+            }
+            keys.retain(|k| k != SCHEMA_VERSION_KEY);
+            keys.sort();
+            keys
+        })?;
+        self.notify_keys(keys.clone());
+        Ok(keys)
+    }
+
+    /// Replace the current settings with backup `index`. The settings being
+    /// replaced are themselves rotated into the backups, so this can be undone.
+    /// Returns the keys that changed.
+    pub fn restore_backup(&self, index: u64) -> Result<Vec<String>, String> {
+        let path = backup_path(&self.inner.data_dir, index);
+        let contents =
+            std::fs::read_to_string(&path).map_err(|_| format!("No settings backup {}", index))?;
+        let backup: serde_json::Value = serde_json::from_str(&contents)
+            .map_err(|e| format!("Settings backup {} is corrupt: {}", index, e))?;
+        let serde_json::Value::Object(backup) = migrate_settings(backup) else {
+            return Err(format!("Settings backup {} is not a JSON object", index));
+        };
+
+        let keys = self.modify(false, |fields| replace_fields(fields, backup))?;
+        self.notify_keys(keys.clone());
+        Ok(keys)
+    }
+
+    /// Replace every setting with the defaults. Lifetime wake lock stats carry
+    /// over, since they aren't configuration. The previous file is kept as
+    /// the newest backup. Returns the keys that changed.
+    pub fn reset(&self) -> Result<Vec<String>, String> {
+        let mut defaults = default_settings();
+        let keys = self.modify(false, |fields| {
+            defaults.wake_lock.total_awake_secs = fields
+                .get("wake_lock")
+                .and_then(|wake_lock| wake_lock.get("total_awake_secs"))
+                .and_then(serde_json::Value::as_u64)
+                .unwrap_or(0);
+            replace_fields(fields, defaults.into_fields())
+        })?;
+        self.notify_keys(keys.clone());
+        Ok(keys)
+    }
+
+    /// Write the current settings to `dest_path`, refusing to replace an
+    /// existing file unless `overwrite` is set
+    pub fn export_to(&self, dest_path: &Path, overwrite: bool, pretty: bool) -> Result<(), String> {
+        if dest_path.exists() && !overwrite {
+            return Err(format!("{} already exists", dest_path.display()));
+        }
+        let dest_dir = dest_path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        if !dest_dir.is_dir() {
+            return Err(format!("Directory {} does not exist", dest_dir.display()));
+        }
+
+        write_file_atomically(dest_path, self.export_json(pretty)?.as_bytes())
+    }
+
+    /// The current settings as they're written by an export
+    pub fn export_json(&self, pretty: bool) -> Result<String, String> {
+        let settings = self.snapshot();
+        if pretty {
+            serde_json::to_string_pretty(&settings)
+        } else {
+            serde_json::to_string(&settings)
+        }
+        .map_err(|e| format!("Failed to serialize settings: {}", e))
+    }
+
+    /// Run `modify` against the current settings and update the cache.
+    ///
+    /// When `debounced`, the write is deferred so bursts of changes coalesce
+    /// into a single write; otherwise the file is written before the cache is
+    /// updated. The write lock is held from the read through the write so
+    /// concurrent updates can't drop each other's changes.
+    fn modify<T>(
+        &self,
+        debounced: bool,
+        modify: impl FnOnce(&mut serde_json::Map<String, serde_json::Value>) -> T,
+    ) -> Result<T, String> {
+        let lock = self.inner.write_lock.lock().unwrap();
+        let current = self.settings();
+        if current.schema_version > CURRENT_SCHEMA_VERSION {
+            return Err("Settings were saved by a newer version of Pipali".to_string());
+        }
+        if self.inner.read_only.load(Ordering::Acquire) {
+            return Err(format!(
+                "{} couldn't be read, so changes aren't saved",
+                SETTINGS_FILE
+            ));
+        }
+        let mut fields = current.into_fields();
+        let result = modify(&mut fields);
+        let settings = Settings::from_value(serde_json::Value::Object(fields))?;
+        let debounce = Duration::from_millis(
+            settings
+                .settings_debounce_ms
+                .unwrap_or(DEFAULT_SETTINGS_DEBOUNCE_MS),
+        );
+        if debounced && !debounce.is_zero() {
+            *self.inner.cache.write().unwrap() = settings;
+            self.inner.dirty.store(true, Ordering::Release);
+            drop(lock);
+            self.schedule_flush(debounce);
+        } else {
+            let hash = write_settings(&self.inner.data_dir, &settings.to_value())?;
+            *self.inner.disk_hash.lock().unwrap() = Some(hash);
+            *self.inner.cache.write().unwrap() = settings;
+            self.inner.dirty.store(false, Ordering::Release);
+        }
+        Ok(result)
+    }
+
+    /// Flush after `delay` unless a flush is already scheduled
+    fn schedule_flush(&self, delay: Duration) {
+        if self.inner.flush_scheduled.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let store = self.clone();
+        let spawned = std::thread::Builder::new()
+            .name("settings-flush".into())
+            .spawn(move || {
+                std::thread::sleep(delay);
+                store.inner.flush_scheduled.store(false, Ordering::Release);
+                store.flush();
+            });
+        if let Err(e) = spawned {
+            log::warn!("[Settings] Failed to schedule flush, writing now: {}", e);
+            self.inner.flush_scheduled.store(false, Ordering::Release);
+            self.flush();
+        }
+    }
+
+    /// Write any changes still waiting on the debounce interval
+    pub fn flush(&self) {
+        let _lock = self.inner.write_lock.lock().unwrap();
+        if !self.inner.dirty.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        match write_settings(&self.inner.data_dir, &self.snapshot()) {
+            Ok(hash) => *self.inner.disk_hash.lock().unwrap() = Some(hash),
+            Err(e) => {
+                log::warn!("[Settings] Failed to save settings: {}", e);
+                self.inner.dirty.store(true, Ordering::Release);
+            }
+        }
+    }
+
+    fn notify_keys(&self, keys: Vec<String>) {
+        self.notify(SettingsChanged {
+            keys,
+            key: None,
+            value: None,
+        });
+    }
+
+    fn notify(&self, change: SettingsChanged) {
+        if let Some(sink) = self.inner.change_sink.get() {
+            sink(&change);
+        }
+    }
+}
+
+/// Tauri command to read a single preference
+#[tauri::command]
+pub fn get_setting(store: State<'_, SettingsStore>, key: String) -> Option<serde_json::Value> {
+    store.get(&key)
+}
+
+/// Tauri command to persist a single preference
+#[tauri::command]
+pub fn set_setting(
+    store: State<'_, SettingsStore>,
+    key: String,
+    value: serde_json::Value,
+) -> Result<(), String> {
+    store.set(&key, value)?;
+    log::info!("[Settings] Saved {}", key);
+    Ok(())
+}
+
+/// Tauri command to read a preference from a module's namespace, so
+/// features can use short key names without colliding
+#[tauri::command]
+pub fn get_setting_ns(
+    store: State<'_, SettingsStore>,
+    module: String,
+    key: String,
+) -> Option<serde_json::Value> {
+    store.get_ns(&module, &key)
+}
+
+/// Tauri command to persist a preference in a module's namespace
+#[tauri::command]
+pub fn set_setting_ns(
+    store: State<'_, SettingsStore>,
+    module: String,
+    key: String,
+    value: serde_json::Value,
+) -> Result<(), String> {
+    store.set_ns(&module, &key, value)?;
+    log::info!("[Settings] Saved {}.{}", module, key);
+    Ok(())
+}
+
+/// Tauri command to persist several preferences in one write
+#[tauri::command]
+pub fn set_settings(
+    store: State<'_, SettingsStore>,
+    values: serde_json::Map<String, serde_json::Value>,
+) -> Result<(), String> {
+    let count = values.len();
+    store.set_many(values)?;
+    log::info!("[Settings] Saved {} preferences", count);
+    Ok(())
+}
+
+/// Tauri command to export settings to a user-chosen file
+#[tauri::command]
+pub fn export_settings(
+    store: State<'_, SettingsStore>,
+    dest_path: String,
+    overwrite: Option<bool>,
+    pretty: Option<bool>,
+) -> Result<(), String> {
+    store.export_to(
+        Path::new(&dest_path),
+        overwrite.unwrap_or(false),
+        pretty.unwrap_or(true),
+    )?;
+    log::info!("[Settings] Exported settings to {}", dest_path);
+    Ok(())
+}
+
+/// Tauri command to export settings to a location picked in the save
+/// dialog. Returns the saved path, or `None` if the dialog was cancelled.
+#[tauri::command]
+pub async fn export_settings_with_dialog(
+    app: AppHandle,
+    pretty: Option<bool>,
+) -> Result<Option<String>, String> {
+    let contents = app
+        .state::<SettingsStore>()
+        .export_json(pretty.unwrap_or(true))?;
+    save_with_dialog(
+        &app,
+        "Export Settings",
+        "pipali-settings.json",
+        Some(("JSON", &["json"])),
+        contents.as_bytes(),
+    )
+}
+
+/// Tauri command to import settings from a file and apply them
+#[tauri::command]
+pub fn import_settings(app: AppHandle, src_path: String, merge: bool) -> Result<(), String> {
+    let store: State<SettingsStore> = app.state();
+    let keys = store.import_from(Path::new(&src_path), merge)?;
+    log::info!(
+        "[Settings] Imported {} preferences from {}",
+        keys.len(),
+        src_path
+    );
+    Ok(())
+}
+
+/// Tauri command to roll settings back to backup `index`, where 1 is the most recent
+#[tauri::command]
+pub fn restore_settings_backup(app: AppHandle, index: u64) -> Result<(), String> {
+    let store: State<SettingsStore> = app.state();
+    store.restore_backup(index)?;
+    log::info!("[Settings] Restored settings backup {}", index);
+    Ok(())
+}
+
+/// Tauri command to restore default settings, backing up the current file first
+#[tauri::command]
+pub fn reset_settings(app: AppHandle) -> Result<(), String> {
+    // Released first, so the awake time it saves is in place before the reset
+    // carries it over, and the reset preferences are applied to a clean state
+    app.state::<WakeLockState>().release_all();
+    let store: State<SettingsStore> = app.state();
+    let keys = store.reset()?;
+    log::info!("[Settings] Reset {} preferences to defaults", keys.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_dir;
+
+    fn read_raw_settings(data_dir: &Path) -> Option<serde_json::Value> {
+        parse_settings_file(&data_dir.join(SETTINGS_FILE))
+    }
+
+    #[test]
+    fn target_is_never_partially_written() {
+        let dir = test_dir("atomic-settings");
+        write_settings(&dir, &serde_json::json!({})).unwrap();
+
+        let done = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let dir = dir.clone();
+            let done = done.clone();
+            std::thread::spawn(move || {
+                let mut reads = 0;
+                while !done.load(Ordering::SeqCst) {
+                    let contents = std::fs::read_to_string(dir.join(SETTINGS_FILE)).unwrap();
+                    serde_json::from_str::<serde_json::Value>(&contents)
+                        .expect("settings file should always be valid JSON");
+                    reads += 1;
+                }
+                reads
+            })
+        };
+
+        let large: Vec<String> = (0..20_000).map(|i| format!("value-{}", i)).collect();
+        for i in 0..50 {
+            let value = serde_json::json!({ "iteration": i, "large": large });
+            write_settings(&dir, &value).unwrap();
+        }
+        done.store(true, Ordering::SeqCst);
+        assert!(reader.join().unwrap() > 0);
+
+        let settings = read_raw_settings(&dir).unwrap();
+        assert_eq!(settings["iteration"], 49);
+        assert_eq!(settings["large"].as_array().unwrap().len(), large.len());
+        assert!(!dir.join(SETTINGS_TMP_FILE).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn migrates_v0_settings_to_current() {
+        let migrated = migrate_settings(serde_json::json!({
+            "awake": true,
+            "keep_awake_on_battery": false,
+            "theme": "dark",
+        }));
+        assert_eq!(
+            migrated,
+            serde_json::json!({
+                "wake_lock": { "keep_awake": true, "keep_awake_on_battery": false },
+                "theme": "dark",
+                "schema_version": CURRENT_SCHEMA_VERSION,
+            })
+        );
+    }
+
+    #[test]
+    fn migration_keeps_existing_keep_awake() {
+        let migrated = migrate_settings(serde_json::json!({ "awake": true, "keep_awake": false }));
+        assert_eq!(migrated["wake_lock"]["keep_awake"], false);
+        assert!(migrated.get("awake").is_none());
+        assert!(migrated.get("keep_awake").is_none());
+    }
+
+    #[test]
+    fn loading_v0_file_writes_back_migrated_settings() {
+        let dir = test_dir("migrate-settings");
+        std::fs::write(dir.join(SETTINGS_FILE), r#"{ "awake": true }"#).unwrap();
+
+        let store = SettingsStore::load(&dir);
+        assert!(store.settings().wake_lock.keep_awake);
+        assert_eq!(
+            store.get_ns("wake_lock", "keep_awake"),
+            Some(serde_json::json!(true))
+        );
+
+        let on_disk = read_raw_settings(&dir).unwrap();
+        assert_eq!(on_disk["wake_lock"]["keep_awake"], true);
+        assert_eq!(schema_version(&on_disk), CURRENT_SCHEMA_VERSION);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn newer_schema_is_loaded_read_only() {
+        let dir = test_dir("newer-settings");
+        let newer = serde_json::json!({ "schema_version": CURRENT_SCHEMA_VERSION + 1, "awake": 1 });
+        std::fs::write(dir.join(SETTINGS_FILE), newer.to_string()).unwrap();
+
+        let store = SettingsStore::load(&dir);
+        assert_eq!(store.get("awake"), Some(serde_json::json!(1)));
+        assert_eq!(store.settings().schema_version, CURRENT_SCHEMA_VERSION + 1);
+        assert!(store.set("keep_awake", serde_json::json!(true)).is_err());
+        assert_eq!(read_raw_settings(&dir).unwrap(), newer);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unreadable_file_is_left_in_place_and_read_only() {
+        let dir = test_dir("unreadable-settings");
+        // A directory can't be read as a file on any platform
+        std::fs::create_dir(dir.join(SETTINGS_FILE)).unwrap();
+
+        let store = SettingsStore::load(&dir);
+        assert_eq!(store.settings().schema_version, CURRENT_SCHEMA_VERSION);
+        assert!(store.set("keep_awake", serde_json::json!(true)).is_err());
+        assert!(dir.join(SETTINGS_FILE).is_dir());
+        let set_aside = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(Result::ok)
+            .any(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with("settings.corrupt.")
+            });
+        assert!(!set_aside);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reset_keeps_lifetime_awake_time() {
+        let dir = test_dir("reset-settings");
+        let store = SettingsStore::load(&dir);
+        store
+            .set(
+                "wake_lock",
+                serde_json::json!({ "keep_awake": true, "total_awake_secs": 90 }),
+            )
+            .unwrap();
+        store.set("theme", serde_json::json!("dark")).unwrap();
+
+        store.reset().unwrap();
+        let wake_lock = store.settings().wake_lock;
+        assert!(!wake_lock.keep_awake);
+        assert_eq!(wake_lock.total_awake_secs, 90);
+        assert_eq!(store.get("theme"), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn concurrent_updates_keep_every_key() {
+        let dir = test_dir("concurrent-settings");
+        let store = SettingsStore::load(&dir);
+        let writers: Vec<_> = (0..32)
+            .map(|i| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    store
+                        .set(&format!("key_{}", i), serde_json::json!(i))
+                        .unwrap();
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        store.flush();
+
+        let settings = read_raw_settings(&dir).unwrap();
+        assert_eq!(settings, store.snapshot());
+        for i in 0..32 {
+            assert_eq!(settings[format!("key_{}", i)], i);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn writes_rotate_previous_versions_into_backups() {
+        let dir = test_dir("backup-settings");
+        let store = SettingsStore::load(&dir);
+        store
+            .set(SETTINGS_DEBOUNCE_MS_KEY, serde_json::json!(0))
+            .unwrap();
+        for i in 0..5 {
+            store.set("counter", serde_json::json!(i)).unwrap();
+        }
+
+        let counter_in = |path: PathBuf| {
+            let contents = std::fs::read_to_string(path).unwrap();
+            serde_json::from_str::<serde_json::Value>(&contents).unwrap()["counter"].clone()
+        };
+        assert_eq!(counter_in(dir.join(SETTINGS_FILE)), 4);
+        assert_eq!(counter_in(backup_path(&dir, 1)), 3);
+        assert_eq!(counter_in(backup_path(&dir, 2)), 2);
+        assert_eq!(counter_in(backup_path(&dir, 3)), 1);
+        assert!(!backup_path(&dir, 4).exists());
+
+        let changed = store.restore_backup(2).unwrap();
+        assert_eq!(changed, vec!["counter".to_string()]);
+        assert_eq!(store.get("counter"), Some(serde_json::json!(2)));
+        assert_eq!(counter_in(backup_path(&dir, 1)), 4);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rapid_changes_are_coalesced_into_one_write() {
+        let dir = test_dir("debounce-settings");
+        let store = SettingsStore::load(&dir);
+        // Long enough that the scheduled flush never fires during the test
+        store
+            .set(SETTINGS_DEBOUNCE_MS_KEY, serde_json::json!(3_600_000))
+            .unwrap();
+        for i in 0..10 {
+            store.set("slider", serde_json::json!(i)).unwrap();
+        }
+
+        assert_eq!(store.get("slider"), Some(serde_json::json!(9)));
+        assert!(read_raw_settings(&dir).is_none());
+        store.flush();
+        assert_eq!(read_raw_settings(&dir).unwrap()["slider"], 9);
+        assert!(!backup_path(&dir, 1).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unknown_keys_survive_a_round_trip() {
+        let value = serde_json::json!({
+            "schema_version": CURRENT_SCHEMA_VERSION,
+            "wake_lock": { "keep_awake": true, "future_flag": 1 },
+            "theme": "dark",
+            "future": { "nested": [1, 2, 3] },
+        });
+        let mut settings = Settings::from_value(value).unwrap();
+        assert!(settings.wake_lock.keep_awake);
+        settings.wake_lock.keep_display_awake = true;
+
+        let round_trip = settings.to_value();
+        assert_eq!(round_trip["theme"], "dark");
+        assert_eq!(
+            round_trip["future"],
+            serde_json::json!({ "nested": [1, 2, 3] })
+        );
+        assert_eq!(round_trip["wake_lock"]["keep_display_awake"], true);
+        assert_eq!(round_trip["wake_lock"]["future_flag"], 1);
+        let invalid = serde_json::json!({ "wake_lock": { "keep_awake": "yes" } });
+        assert!(Settings::from_value(invalid).is_err());
+    }
+
+    #[test]
+    fn show_hide_shortcut_must_parse() {
+        let check = |combo| validate_settings(&serde_json::json!({ "show_hide_shortcut": combo }));
+        assert!(check(serde_json::json!("CommandOrControl+Shift+P")).is_ok());
+        assert!(check(serde_json::json!("Alt+Nope")).is_err());
+        assert!(check(serde_json::json!(42)).is_err());
+    }
+
+    #[test]
+    fn deep_merge_keeps_nested_siblings() {
+        let mut settings = serde_json::json!({
+            "ui": { "theme": "light", "fontSize": 14, "panels": { "left": true } },
+            "keep_awake": true,
+        });
+        deep_merge(
+            &mut settings,
+            serde_json::json!({ "ui": { "theme": "dark", "panels": { "right": false } } }),
+        );
+        assert_eq!(
+            settings,
+            serde_json::json!({
+                "ui": {
+                    "theme": "dark",
+                    "fontSize": 14,
+                    "panels": { "left": true, "right": false },
+                },
+                "keep_awake": true,
+            })
+        );
+    }
+
+    #[test]
+    fn deep_merge_replaces_arrays_and_scalars() {
+        let mut settings = serde_json::json!({ "tags": [1, 2, 3], "ui": { "zoom": 1 } });
+        deep_merge(
+            &mut settings,
+            serde_json::json!({ "tags": [4], "ui": "compact" }),
+        );
+        assert_eq!(
+            settings,
+            serde_json::json!({ "tags": [4], "ui": "compact" })
+        );
+    }
+
+    #[test]
+    fn deep_merge_null_deletes_keys() {
+        let mut settings = serde_json::json!({
+            "ui": { "theme": "dark", "fontSize": 14 },
+            "old": 1,
+        });
+        deep_merge(
+            &mut settings,
+            serde_json::json!({
+                "ui": { "fontSize": null },
+                "old": null,
+                "new": { "a": null, "b": 2 },
+            }),
+        );
+        assert_eq!(
+            settings,
+            serde_json::json!({ "ui": { "theme": "dark" }, "new": { "b": 2 } })
+        );
+    }
+
+    #[test]
+    fn set_merges_nested_patches() {
+        let dir = test_dir("merge-settings");
+        let store = SettingsStore::load(&dir);
+        store
+            .set(
+                "ui",
+                serde_json::json!({ "theme": "light", "fontSize": 14 }),
+            )
+            .unwrap();
+        store
+            .set("ui", serde_json::json!({ "theme": "dark" }))
+            .unwrap();
+        assert_eq!(
+            store.get("ui"),
+            Some(serde_json::json!({ "theme": "dark", "fontSize": 14 }))
+        );
+        store.set("ui", serde_json::Value::Null).unwrap();
+        assert_eq!(store.get("ui"), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn namespaced_keys_do_not_collide() {
+        let dir = test_dir("namespaced-settings");
+        let store = SettingsStore::load(&dir);
+        store
+            .set_ns("wake_lock", "keep_awake", serde_json::json!(true))
+            .unwrap();
+        store
+            .set_ns("sync", "enabled", serde_json::json!(true))
+            .unwrap();
+        store
+            .set_ns("updater", "enabled", serde_json::json!(false))
+            .unwrap();
+        assert_eq!(
+            store.get_ns("sync", "enabled"),
+            Some(serde_json::json!(true))
+        );
+        assert_eq!(
+            store.get_ns("updater", "enabled"),
+            Some(serde_json::json!(false))
+        );
+        assert_eq!(store.get_ns("missing", "enabled"), None);
+        assert!(store.settings().wake_lock.keep_awake);
+        assert!(store
+            .set_ns("wake_lock", "keep_awake", serde_json::json!("yes"))
+            .is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    fn corrupt_files(dir: &Path) -> Vec<PathBuf> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with("settings.corrupt."))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn corrupt_file_is_preserved_and_backup_restored() {
+        let dir = test_dir("corrupt-with-backup");
+        let store = SettingsStore::load(&dir);
+        store
+            .set(SETTINGS_DEBOUNCE_MS_KEY, serde_json::json!(0))
+            .unwrap();
+        store.set("keep_awake", serde_json::json!(true)).unwrap();
+        std::fs::write(dir.join(SETTINGS_FILE), r#"{ "keep_awake": tr"#).unwrap();
+
+        let store = SettingsStore::load(&dir);
+        assert_eq!(
+            store.get(SETTINGS_DEBOUNCE_MS_KEY),
+            Some(serde_json::json!(0))
+        );
+        let corrupt = corrupt_files(&dir);
+        assert_eq!(corrupt.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&corrupt[0]).unwrap(),
+            r#"{ "keep_awake": tr"#
+        );
+        assert!(read_raw_settings(&dir).is_some());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn corrupt_file_without_backup_starts_from_defaults() {
+        let dir = test_dir("corrupt-without-backup");
+        std::fs::write(dir.join(SETTINGS_FILE), "not json").unwrap();
+
+        let store = SettingsStore::load(&dir);
+        assert_eq!(store.settings().schema_version, CURRENT_SCHEMA_VERSION);
+        assert!(!store.settings().wake_lock.keep_awake);
+        let corrupt = corrupt_files(&dir);
+        assert_eq!(corrupt.len(), 1);
+        assert_eq!(std::fs::read_to_string(&corrupt[0]).unwrap(), "not json");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,109 @@
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Listener, Manager, State};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+
+use crate::settings::{self, SettingsStore};
+
+/// Settings key holding the key combo that shows and hides the main window
+pub const SHOW_HIDE_SHORTCUT_KEY: &str = "show_hide_shortcut";
+
+/// Used until the user picks their own combo
+const DEFAULT_SHOW_HIDE_SHORTCUT: &str = "Alt+Space";
+
+/// Emitted with `{ shortcut, error }` when a new combo can't be registered
+const REGISTRATION_FAILED_EVENT: &str = "shortcut-registration-failed";
+
+/// The show/hide shortcut currently registered with the OS
+#[derive(Default)]
+pub struct ShortcutState {
+    current: Mutex<Option<Shortcut>>,
+}
+
+/// The combo configured in settings, or the default
+fn configured_shortcut(store: &SettingsStore) -> String {
+    store
+        .settings()
+        .show_hide_shortcut
+        .unwrap_or_else(|| DEFAULT_SHOW_HIDE_SHORTCUT.to_string())
+}
+
+/// Bind `combo` to show and hide the main window. The new combo is
+/// registered before the old one is released, so if it's taken by another
+/// app the previous binding keeps working.
+fn register_show_hide_shortcut(app: &AppHandle, combo: &str) -> Result<(), String> {
+    let shortcut: Shortcut = combo
+        .parse()
+        .map_err(|e| format!("Invalid shortcut '{}': {}", combo, e))?;
+    let state: State<ShortcutState> = app.state();
+    let mut current = state.current.lock().unwrap();
+    if *current == Some(shortcut) {
+        return Ok(());
+    }
+
+    app.global_shortcut().register(shortcut).map_err(|e| {
+        format!(
+            "Couldn't register {} (it may be in use by another app): {}",
+            combo, e
+        )
+    })?;
+    if let Some(previous) = current.replace(shortcut) {
+        if let Err(e) = app.global_shortcut().unregister(previous) {
+            log::warn!("[App] Failed to release previous shortcut: {}", e);
+        }
+    }
+    log::info!("[App] Global shortcut {} registered", combo);
+    Ok(())
+}
+
+/// Register the configured shortcut, and re-register it whenever the
+/// setting changes. A saved combo that can't be registered is rolled back
+/// to the one still active.
+pub fn init_show_hide_shortcut(app: &AppHandle, store: &SettingsStore) {
+    if let Err(e) = register_show_hide_shortcut(app, &configured_shortcut(store)) {
+        log::warn!("[App] {}", e);
+    }
+
+    let app_handle = app.clone();
+    app.listen(settings::SETTINGS_CHANGED_EVENT, move |event| {
+        let Ok(changed) = serde_json::from_str::<settings::SettingsChanged>(event.payload()) else {
+            return;
+        };
+        if !changed.keys.iter().any(|key| key == SHOW_HIDE_SHORTCUT_KEY) {
+            return;
+        }
+        let store: State<SettingsStore> = app_handle.state();
+        let combo = configured_shortcut(&store);
+        if let Err(e) = register_show_hide_shortcut(&app_handle, &combo) {
+            log::error!("[App] {}", e);
+            // Put the working combo back so settings match what's registered
+            let state: State<ShortcutState> = app_handle.state();
+            let active = state
+                .current
+                .lock()
+                .unwrap()
+                .map(|shortcut| shortcut.to_string());
+            if let Some(active) = active {
+                let restored = store.update(|settings| settings.show_hide_shortcut = Some(active));
+                if let Err(e) = restored {
+                    log::warn!("[App] Failed to restore the show/hide shortcut: {}", e);
+                }
+            }
+            let _ = app_handle.emit(
+                REGISTRATION_FAILED_EVENT,
+                serde_json::json!({ "shortcut": combo, "error": e }),
+            );
+        }
+    });
+}
+
+/// Change the show/hide shortcut. The combo is only saved once it's
+/// registered, so one that's taken or invalid never replaces the working one.
+#[tauri::command]
+pub fn set_show_hide_shortcut(
+    app: AppHandle,
+    store: State<'_, SettingsStore>,
+    shortcut: String,
+) -> Result<(), String> {
+    register_show_hide_shortcut(&app, &shortcut)?;
+    store.update(|settings| settings.show_hide_shortcut = Some(shortcut))
+}
@@ -0,0 +1,222 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const SIDECAR_LOG_FILE: &str = "sidecar.log";
+/// Rotate the log once it would grow past this size
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+/// How many rotated logs to keep next to the live one
+const MAX_ROTATED_LOGS: u32 = 3;
+/// Emit a partial line anyway once this much output arrives without a newline
+const MAX_PENDING_LINE_BYTES: usize = 64 * 1024;
+
+/// Reassembles raw output chunks into whole lines, holding back any partial
+/// line until its newline arrives
+#[derive(Default)]
+pub struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Add a chunk of output, returning every line it completed
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            lines.push(decode_line(&line));
+        }
+        if self.pending.len() >= MAX_PENDING_LINE_BYTES {
+            lines.push(decode_line(&std::mem::take(&mut self.pending)));
+        }
+        lines
+    }
+
+    /// Take whatever is left once the stream has ended
+    pub fn finish(&mut self) -> Option<String> {
+        (!self.pending.is_empty()).then(|| decode_line(&std::mem::take(&mut self.pending)))
+    }
+}
+
+fn decode_line(line: &[u8]) -> String {
+    String::from_utf8_lossy(line)
+        .trim_end_matches(['\n', '\r'])
+        .to_string()
+}
+
+/// Appends sidecar output to a log such as `sidecar.log`, rotating it into
+/// `sidecar.log.1`..`sidecar.log.N` when it exceeds the size cap
+pub struct SidecarLog {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_bytes: u64,
+    keep: u32,
+}
+
+impl SidecarLog {
+    /// Open (or create) the log called `file_name` in `data_dir`
+    pub fn open(data_dir: &Path, file_name: &str) -> Result<Self, String> {
+        Self::open_with_limits(data_dir, file_name, MAX_LOG_BYTES, MAX_ROTATED_LOGS)
+    }
+
+    fn open_with_limits(
+        data_dir: &Path,
+        file_name: &str,
+        max_bytes: u64,
+        keep: u32,
+    ) -> Result<Self, String> {
+        let path = data_dir.join(file_name);
+        let file = open_append(&path)?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_bytes,
+            keep,
+        })
+    }
+
+    /// Append one line of output from `stream` ("stdout" or "stderr")
+    pub fn write_line(&mut self, stream: &str, line: &str) {
+        let entry = format!(
+            "{} [{}] {}\n",
+            chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+            stream,
+            line
+        );
+        if self.size > 0 && self.size + entry.len() as u64 > self.max_bytes {
+            if let Err(e) = self.rotate() {
+                log::warn!("[Sidecar] Failed to rotate {}: {}", self.path.display(), e);
+            }
+        }
+        match self.file.write_all(entry.as_bytes()) {
+            Ok(()) => self.size += entry.len() as u64,
+            Err(e) => log::warn!("[Sidecar] Failed to write {}: {}", self.path.display(), e),
+        }
+    }
+
+    /// Truncate the live log and delete every rotated copy
+    pub fn clear(&mut self) -> Result<(), String> {
+        self.file
+            .set_len(0)
+            .map_err(|e| format!("Failed to clear {}: {}", self.path.display(), e))?;
+        self.size = 0;
+        for index in 1..=self.keep {
+            let rotated = self.rotated_path(index);
+            match std::fs::remove_file(&rotated) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(format!("Failed to remove {}: {}", rotated.display(), e));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn rotated_path(&self, index: u32) -> PathBuf {
+        PathBuf::from(format!("{}.{}", self.path.display(), index))
+    }
+
+    /// Shift `sidecar.log.1`..`sidecar.log.N` up by one, dropping the oldest,
+    /// move the live log to `sidecar.log.1` and start a fresh one
+    fn rotate(&mut self) -> Result<(), String> {
+        if self.keep == 0 {
+            self.file.set_len(0).map_err(|e| e.to_string())?;
+            self.size = 0;
+            return Ok(());
+        }
+        let _ = std::fs::remove_file(self.rotated_path(self.keep));
+        for index in (1..self.keep).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                std::fs::rename(&from, self.rotated_path(index + 1)).map_err(|e| e.to_string())?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated_path(1)).map_err(|e| e.to_string())?;
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> Result<File, String> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_dir;
+
+    #[test]
+    fn rotates_when_size_cap_is_exceeded() {
+        let dir = test_dir("sidecar-log-rotate");
+        let mut log = SidecarLog::open_with_limits(&dir, SIDECAR_LOG_FILE, 200, 2).unwrap();
+        for i in 0..20 {
+            log.write_line("stdout", &format!("line {}", i));
+        }
+
+        let live = std::fs::read_to_string(dir.join(SIDECAR_LOG_FILE)).unwrap();
+        assert!(live.len() as u64 <= 200);
+        assert!(live.ends_with("[stdout] line 19\n"));
+        assert!(dir.join("sidecar.log.1").exists());
+        assert!(dir.join("sidecar.log.2").exists());
+        assert!(!dir.join("sidecar.log.3").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn clear_truncates_live_log_and_removes_rotations() {
+        let dir = test_dir("sidecar-log-clear");
+        let mut log = SidecarLog::open_with_limits(&dir, SIDECAR_LOG_FILE, 200, 2).unwrap();
+        for i in 0..20 {
+            log.write_line("stdout", &format!("line {:02} with some padding", i));
+        }
+        assert!(dir.join("sidecar.log.1").exists());
+
+        log.clear().unwrap();
+        assert!(!dir.join("sidecar.log.1").exists());
+        assert!(!dir.join("sidecar.log.2").exists());
+        log.write_line("stdout", "after clear");
+        let live = std::fs::read_to_string(dir.join(SIDECAR_LOG_FILE)).unwrap();
+        assert_eq!(live.lines().count(), 1);
+        assert!(live.ends_with("[stdout] after clear\n"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn appends_across_reopens() {
+        let dir = test_dir("sidecar-log-append");
+        SidecarLog::open(&dir, SIDECAR_LOG_FILE)
+            .unwrap()
+            .write_line("stdout", "first");
+        SidecarLog::open(&dir, SIDECAR_LOG_FILE)
+            .unwrap()
+            .write_line("stderr", "second");
+
+        let live = std::fs::read_to_string(dir.join(SIDECAR_LOG_FILE)).unwrap();
+        let lines: Vec<&str> = live.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("[stdout] first"));
+        assert!(lines[1].ends_with("[stderr] second"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn partial_lines_wait_for_their_newline() {
+        let mut buffer = LineBuffer::default();
+        assert!(buffer.push(b"Listening on ").is_empty());
+        assert_eq!(
+            buffer.push(b"port 6464\r\nReady\nDone"),
+            vec!["Listening on port 6464", "Ready"]
+        );
+        assert_eq!(buffer.finish(), Some("Done".to_string()));
+        assert_eq!(buffer.finish(), None);
+    }
+}
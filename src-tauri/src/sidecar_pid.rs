@@ -0,0 +1,161 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+pub const SIDECAR_PID_FILE: &str = "sidecar.pid";
+/// How long to wait for a killed orphan to exit before starting anyway
+const ORPHAN_KILL_WAIT: Duration = Duration::from_secs(2);
+
+/// Identity of a spawned sidecar, written to `sidecar.pid` in the data
+/// directory right after spawn and deleted once it exits, so that the next
+/// launch can clean it up if the app died without stopping it
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SidecarPidFile {
+    pub pid: u32,
+    /// Executable of the process, to tell our sidecar from an unrelated
+    /// process that reused the PID
+    pub exe: PathBuf,
+    /// When the process started, in seconds since the Unix epoch
+    pub started_at: u64,
+}
+
+/// Executable and start time of the running process `pid`, if it can be determined
+fn process_identity(system: &mut System, pid: Pid) -> Option<(PathBuf, u64)> {
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::nothing().with_exe(UpdateKind::Always),
+    );
+    let process = system.process(pid)?;
+    Some((process.exe()?.to_path_buf(), process.start_time()))
+}
+
+impl SidecarPidFile {
+    /// Describe the running process `pid`
+    pub fn for_process(pid: u32) -> Option<Self> {
+        let (exe, started_at) = process_identity(&mut System::new(), Pid::from_u32(pid))?;
+        Some(Self {
+            pid,
+            exe,
+            started_at,
+        })
+    }
+
+    /// Whether `pid` is still the process this file describes
+    fn matches(&self, exe: &Path, started_at: u64) -> bool {
+        // Start times are whole seconds and can be rounded differently
+        // between reads, so allow a second either way
+        self.exe == exe && self.started_at.abs_diff(started_at) <= 1
+    }
+
+    fn read(path: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, contents)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+/// Delete the PID file once its sidecar has exited
+pub fn remove_pid_file(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            log::warn!("[Sidecar] Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Kill the sidecar recorded in the PID file at `path`, left behind by a run
+/// that didn't shut down cleanly. Only a process whose PID, executable and
+/// start time all match is killed.
+pub fn kill_orphaned_sidecar(path: &Path) {
+    let Some(recorded) = SidecarPidFile::read(path) else {
+        remove_pid_file(path);
+        return;
+    };
+    let pid = Pid::from_u32(recorded.pid);
+    let mut system = System::new();
+    match process_identity(&mut system, pid) {
+        Some((exe, started_at)) if recorded.matches(&exe, started_at) => {
+            log::warn!(
+                "[Sidecar] Killing orphaned sidecar from a previous run (pid={})",
+                recorded.pid
+            );
+            let killed = system.process(pid).is_some_and(|process| process.kill());
+            let deadline = Instant::now() + ORPHAN_KILL_WAIT;
+            while killed
+                && Instant::now() < deadline
+                && process_identity(&mut system, pid).is_some()
+            {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+        }
+        Some(_) => log::info!(
+            "[Sidecar] PID {} from a previous run now belongs to another program, leaving it",
+            recorded.pid
+        ),
+        None => {}
+    }
+    remove_pid_file(path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::test_support::test_dir;
+
+    #[cfg(unix)]
+    #[test]
+    fn kills_only_processes_matching_the_pid_file() {
+        let dir = test_dir("pid-orphan");
+        let path = dir.join("sidecar.pid");
+        let mut orphan = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let mut bystander = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+
+        // Same PID, different executable or start time: left alone
+        let bystander_file = SidecarPidFile::for_process(bystander.id()).unwrap();
+        let impostors = [
+            SidecarPidFile {
+                exe: PathBuf::from("/not/bun"),
+                ..bystander_file
+            },
+            SidecarPidFile {
+                started_at: 1,
+                ..SidecarPidFile::for_process(bystander.id()).unwrap()
+            },
+        ];
+        for impostor in impostors {
+            impostor.write(&path).unwrap();
+            kill_orphaned_sidecar(&path);
+            assert!(bystander.try_wait().unwrap().is_none());
+            assert!(!path.exists());
+        }
+
+        SidecarPidFile::for_process(orphan.id())
+            .unwrap()
+            .write(&path)
+            .unwrap();
+        kill_orphaned_sidecar(&path);
+        // SIGKILL is delivered asynchronously, so give it a moment
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while orphan.try_wait().unwrap().is_none() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(orphan.try_wait().unwrap().is_some());
+        assert!(!path.exists());
+
+        bystander.kill().unwrap();
+        bystander.wait().unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+/// Scheduling priority of the sidecar process, from the `priority` setting
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SidecarPriority {
+    /// Yield CPU to the UI and other foreground apps
+    Low,
+    Normal,
+    /// Usually needs elevated privileges on Unix
+    High,
+}
+
+impl SidecarPriority {
+    #[cfg(unix)]
+    fn niceness(self) -> i32 {
+        match self {
+            Self::Low => 10,
+            Self::Normal => 0,
+            Self::High => -5,
+        }
+    }
+}
+
+/// Apply `priority` to the freshly spawned sidecar `pid`. Failures are only
+/// logged, leaving the sidecar at the priority it inherited.
+pub fn apply_sidecar_priority(pid: u32, priority: SidecarPriority) {
+    log::info!("[Sidecar] Setting priority {:?} (pid={})", priority, pid);
+    if let Err(e) = set_priority(pid, priority) {
+        log::warn!("[Sidecar] Failed to set priority {:?}: {}", priority, e);
+    }
+    match effective_priority(pid) {
+        Ok(effective) => log::info!("[Sidecar] Effective priority: {} (pid={})", effective, pid),
+        Err(e) => log::warn!("[Sidecar] Failed to read back priority: {}", e),
+    }
+}
+
+#[cfg(unix)]
+fn set_priority(pid: u32, priority: SidecarPriority) -> Result<(), String> {
+    let output = std::process::Command::new("renice")
+        .args([&priority.niceness().to_string(), "-p", &pid.to_string()])
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+#[cfg(unix)]
+fn effective_priority(pid: u32) -> Result<String, String> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", "nice=", "-p", &pid.to_string()])
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    let nice = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.status.success() && !nice.is_empty() {
+        Ok(format!("nice {}", nice))
+    } else {
+        Err(format!("ps exited with status {}", output.status))
+    }
+}
+
+#[cfg(windows)]
+mod win {
+    use super::SidecarPriority;
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::System::Threading::{
+        GetPriorityClass, OpenProcess, SetPriorityClass, ABOVE_NORMAL_PRIORITY_CLASS,
+        BELOW_NORMAL_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS, PROCESS_ACCESS_RIGHTS,
+        PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_SET_INFORMATION,
+    };
+
+    /// Run `f` with a handle to `pid` opened for `access`, closing it afterwards
+    fn with_process<T>(
+        pid: u32,
+        access: PROCESS_ACCESS_RIGHTS,
+        f: impl FnOnce(HANDLE) -> Result<T, String>,
+    ) -> Result<T, String> {
+        // SAFETY: plain Win32 calls; the handle is checked before use and
+        // closed exactly once
+        unsafe {
+            let handle = OpenProcess(access, 0, pid);
+            if handle.is_null() {
+                return Err(std::io::Error::last_os_error().to_string());
+            }
+            let result = f(handle);
+            CloseHandle(handle);
+            result
+        }
+    }
+
+    pub fn set_priority(pid: u32, priority: SidecarPriority) -> Result<(), String> {
+        let class = match priority {
+            SidecarPriority::Low => BELOW_NORMAL_PRIORITY_CLASS,
+            SidecarPriority::Normal => NORMAL_PRIORITY_CLASS,
+            SidecarPriority::High => ABOVE_NORMAL_PRIORITY_CLASS,
+        };
+        with_process(pid, PROCESS_SET_INFORMATION, |handle| {
+            // SAFETY: `handle` is a live process handle opened with
+            // PROCESS_SET_INFORMATION
+            if unsafe { SetPriorityClass(handle, class) } != 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error().to_string())
+            }
+        })
+    }
+
+    pub fn effective_priority(pid: u32) -> Result<String, String> {
+        with_process(pid, PROCESS_QUERY_LIMITED_INFORMATION, |handle| {
+            // SAFETY: `handle` is a live process handle opened with
+            // PROCESS_QUERY_LIMITED_INFORMATION
+            let class = unsafe { GetPriorityClass(handle) };
+            match class {
+                0 => Err(std::io::Error::last_os_error().to_string()),
+                BELOW_NORMAL_PRIORITY_CLASS => Ok("below normal".to_string()),
+                NORMAL_PRIORITY_CLASS => Ok("normal".to_string()),
+                ABOVE_NORMAL_PRIORITY_CLASS => Ok("above normal".to_string()),
+                class => Ok(format!("priority class {:#x}", class)),
+            }
+        })
+    }
+}
+
+#[cfg(windows)]
+use win::{effective_priority, set_priority};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priorities_use_lowercase_names() {
+        let low: SidecarPriority = serde_json::from_value(serde_json::json!("low")).unwrap();
+        assert_eq!(low, SidecarPriority::Low);
+        assert!(serde_json::from_value::<SidecarPriority>(serde_json::json!("Low")).is_err());
+        assert!(serde_json::from_value::<SidecarPriority>(serde_json::json!("realtime")).is_err());
+    }
+}
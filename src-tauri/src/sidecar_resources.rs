@@ -0,0 +1,50 @@
+use std::time::Duration;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+use tauri::{AppHandle, Manager};
+
+use crate::sidecar::SidecarState;
+
+/// How often sidecar processes are sampled. CPU usage is measured over the
+/// time between two samples, so this is also its averaging window.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Payload of `get_sidecar_resources`
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SidecarResources {
+    /// CPU usage since the previous sample, where 100 is one fully busy core
+    pub cpu_percent: f32,
+    /// Resident memory of the sidecar process
+    pub memory_bytes: u64,
+    /// Time since the current process was spawned
+    pub uptime_secs: u64,
+}
+
+/// Keep sampling the CPU and memory usage of running sidecars, so
+/// `get_sidecar_resources` can answer straight from the latest snapshot
+pub async fn sample_sidecar_resources(app: AppHandle) {
+    let mut system = System::new();
+    let refresh = ProcessRefreshKind::nothing().with_cpu().with_memory();
+    loop {
+        let sidecars = app.state::<SidecarState>().instances();
+        let pids: Vec<Pid> = sidecars
+            .iter()
+            .filter_map(|sidecar| sidecar.pid())
+            .map(Pid::from_u32)
+            .collect();
+        system.refresh_processes_specifics(ProcessesToUpdate::Some(&pids), true, refresh);
+
+        for sidecar in sidecars {
+            let snapshot = sidecar
+                .pid()
+                .and_then(|pid| system.process(Pid::from_u32(pid)))
+                .map(|process| SidecarResources {
+                    cpu_percent: process.cpu_usage(),
+                    memory_bytes: process.memory(),
+                    uptime_secs: sidecar.uptime().map_or(0, |uptime| uptime.as_secs()),
+                });
+            sidecar.set_resources(snapshot);
+        }
+        tokio::time::sleep(SAMPLE_INTERVAL).await;
+    }
+}
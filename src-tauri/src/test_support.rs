@@ -0,0 +1,11 @@
+//! Fixtures shared by the unit tests
+
+use std::path::PathBuf;
+
+/// An empty scratch directory for one test, named after `name`
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("pipali-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
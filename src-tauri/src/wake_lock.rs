@@ -1,131 +1,1610 @@
-use std::path::{Path, PathBuf};
+use chrono::{Datelike, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Mutex;
-use tauri::State;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Listener, Manager, State};
 
-const SETTINGS_FILE: &str = "settings.json";
+use crate::settings::{self, Settings, SettingsChanged, SettingsStore};
+
+/// Settings key holding every wake lock preference
+const SETTINGS_KEY: &str = "wake_lock";
+
+/// Receives wake lock events along with the status at the time they fired
+type EventSink = Box<dyn Fn(&str, &WakeLockStatus) + Send + Sync>;
+
+/// Force-release a guard held continuously for longer than this
+pub(crate) const DEFAULT_MAX_WAKE_LOCK_MINUTES: u64 = 240;
+/// Tag under which the schedule holds its reference
+const SCHEDULE_TAG: &str = "schedule";
+/// Tag under which in-flight sidecar work holds its references
+const SIDECAR_WORK_TAG: &str = "sidecar";
+/// Release all sidecar work references if no new work starts for this long,
+/// in case a completion event was missed
+const SIDECAR_WORK_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+pub(crate) const CHANGED_EVENT: &str = "wake-lock-changed";
+const SUPPRESSED_EVENT: &str = "wake-lock-suppressed";
+const EXPIRED_EVENT: &str = "wake-lock-expired";
+/// How often to check whether the machine is running on battery
+const POWER_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Wall-clock time beyond the poll interval that indicates the system was
+/// suspended between two polls, where there are no native wake notifications
+const RESUME_GAP_THRESHOLD: Duration = Duration::from_secs(15);
+
+/// What a wake lock reference keeps awake.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WakeLockMode {
+    /// Prevent idle sleep, but let the display dim and turn off
+    Idle,
+    /// Prevent idle sleep and keep the display on
+    Display,
+}
+
+/// A held sleep inhibition. Dropping it lets the system sleep again.
+pub trait Guard: Send {}
+
+impl Guard for keepawake::KeepAwake {}
+
+/// Source of sleep inhibitions, so the OS integration can be swapped out
+pub trait SleepInhibitor: Send + Sync {
+    fn acquire(&self, display: bool, idle: bool) -> Result<Box<dyn Guard>, String>;
+}
+
+/// Inhibits sleep through the OS via `keepawake`
+struct KeepAwakeInhibitor;
+
+impl SleepInhibitor for KeepAwakeInhibitor {
+    fn acquire(&self, display: bool, idle: bool) -> Result<Box<dyn Guard>, String> {
+        let guard = keepawake::Builder::default()
+            .display(display)
+            .idle(idle)
+            .create()
+            .map_err(|e| e.to_string())?;
+        Ok(Box::new(guard))
+    }
+}
+
+/// A reference held on behalf of a named subsystem
+struct TaggedHold {
+    tag: String,
+    reason: Option<String>,
+}
+
+/// The held guard along with the flags it was created with
+struct ActiveGuard {
+    _inner: Box<dyn Guard>,
+    display: bool,
+}
+
+/// Snapshot of the wake lock state (exposed to frontend)
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WakeLockStatus {
+    /// Whether a guard is currently held
+    pub active: bool,
+    /// Total references across all acquirers and modes
+    pub count: u32,
+    pub user_enabled: bool,
+    pub user_display_enabled: bool,
+    pub on_battery: bool,
+    /// Whether the lock is wanted but withheld because the machine is on battery
+    pub suppressed: bool,
+    /// Reference counts of tagged acquirers currently holding the lock
+    pub tags: BTreeMap<String, u32>,
+    /// Why the lock is held, one entry per tagged reference that gave a reason,
+    /// oldest first
+    pub reasons: Vec<String>,
+    /// How long the guard has been held this session
+    pub total_awake_secs: u64,
+    /// How long the guard has been held across all sessions
+    pub lifetime_awake_secs: u64,
+    /// When the current hold started, in Unix epoch milliseconds
+    pub currently_active_since: Option<u64>,
+}
 
 pub struct WakeLockState {
+    inhibitor: Box<dyn SleepInhibitor>,
     count: Mutex<u32>,
-    guard: Mutex<Option<keepawake::KeepAwake>>,
+    display_count: Mutex<u32>,
+    guard: Mutex<Option<ActiveGuard>>,
     user_enabled: Mutex<bool>,
-    data_dir: Mutex<Option<PathBuf>>,
+    user_display_enabled: Mutex<bool>,
+    settings: Mutex<Option<SettingsStore>>,
+    event_sink: Mutex<Option<EventSink>>,
+    /// Set when the guard is created or dropped, cleared once the
+    /// `wake-lock-changed` event has been emitted
+    changed: AtomicBool,
+    on_battery: AtomicBool,
+    keep_awake_on_battery: AtomicBool,
+    /// Whether the guard is currently withheld because of battery power
+    suppressed: AtomicBool,
+    /// Set when the lock becomes suppressed, cleared once `wake-lock-suppressed` is emitted
+    suppressed_pending: AtomicBool,
+    /// Outstanding timed wake locks, keyed by the id handed to the frontend.
+    /// Each entry holds one reference until it fires or is cancelled.
+    timers: Mutex<HashMap<u64, JoinHandle<()>>>,
+    next_timer_id: AtomicU64,
+    /// Tagged references in acquisition order. Each one also holds one idle
+    /// reference in `count`.
+    holds: Mutex<Vec<TaggedHold>>,
+    /// Keep-awake windows, and whether the schedule currently holds a reference
+    schedule: Mutex<(Vec<ParsedWindow>, bool)>,
+    /// Set when the cap force-releases during a schedule window, so the
+    /// schedule stays off until that window closes instead of re-acquiring
+    schedule_expired: AtomicBool,
+    /// Safety timer that releases sidecar work references if completions are missed
+    sidecar_watchdog: Mutex<Option<JoinHandle<()>>>,
+    /// Start of the current hold, as a monotonic instant and a wall-clock time
+    active_since: Mutex<Option<(Instant, SystemTime)>>,
+    /// Time held this session, excluding the current hold
+    total_awake: Mutex<Duration>,
+    /// Set when a hold ends, cleared once the lifetime total is persisted
+    awake_unsaved: AtomicBool,
+    /// Time held in previous sessions, as loaded from settings
+    previous_awake: Mutex<Duration>,
+    /// Longest a guard may be held continuously before it is force-released.
+    /// Zero disables the cap.
+    max_duration: Mutex<Duration>,
+    /// Held while a preference is applied and persisted, and while saved
+    /// preferences are reloaded, so a reload never sees one without the other
+    preferences: Mutex<()>,
 }
 
 impl Default for WakeLockState {
     fn default() -> Self {
+        Self::with_inhibitor(Box::new(KeepAwakeInhibitor))
+    }
+}
+
+/// A daily time range during which the system is kept awake.
+/// A window whose end is before its start runs past midnight into the next day.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScheduleWindow {
+    /// Local start time as "HH:MM"
+    pub start: String,
+    /// Local end time as "HH:MM" (exclusive)
+    pub end: String,
+    /// Days the window starts on ("mon", "tuesday", ...). Empty means every day.
+    #[serde(default)]
+    pub days: Vec<String>,
+}
+
+/// Wake lock preferences, stored under the `wake_lock` settings namespace
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct WakeLockSettings {
+    pub keep_awake: bool,
+    pub keep_display_awake: bool,
+    pub keep_awake_on_battery: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub keep_awake_schedule: Vec<ScheduleWindow>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_wake_lock_minutes: Option<u64>,
+    /// Lifetime awake time across sessions, in seconds
+    pub total_awake_secs: u64,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A `ScheduleWindow` parsed into minutes since midnight
+#[derive(Clone, Debug)]
+struct ParsedWindow {
+    start: u32,
+    end: u32,
+    days: Vec<Weekday>,
+}
+
+impl ScheduleWindow {
+    fn parse(&self) -> Result<ParsedWindow, String> {
+        let start = parse_hhmm(&self.start)
+            .ok_or_else(|| format!("Invalid start time '{}', expected HH:MM", self.start))?;
+        let end = parse_hhmm(&self.end)
+            .ok_or_else(|| format!("Invalid end time '{}', expected HH:MM", self.end))?;
+        let days = self
+            .days
+            .iter()
+            .map(|d| {
+                d.parse::<Weekday>()
+                    .map_err(|_| format!("Invalid day '{}'", d))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ParsedWindow { start, end, days })
+    }
+}
+
+impl ParsedWindow {
+    fn starts_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    /// Whether the window is open at `minute` past midnight on `day`
+    fn contains(&self, day: Weekday, minute: u32) -> bool {
+        if self.start <= self.end {
+            self.starts_on(day) && self.start <= minute && minute < self.end
+        } else {
+            // Spans midnight: either the evening part of a window starting
+            // today, or the morning part of one that started yesterday
+            (self.starts_on(day) && minute >= self.start)
+                || (self.starts_on(day.pred()) && minute < self.end)
+        }
+    }
+}
+
+fn parse_hhmm(value: &str) -> Option<u32> {
+    let (hours, minutes) = value.trim().split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+/// Parse the saved schedule, skipping any windows that fail to parse
+fn parse_schedule(windows: &[ScheduleWindow]) -> Vec<ParsedWindow> {
+    windows
+        .iter()
+        .filter_map(|w| match w.parse() {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                log::warn!("[WakeLock] Ignoring schedule window: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+impl WakeLockState {
+    pub fn with_inhibitor(inhibitor: Box<dyn SleepInhibitor>) -> Self {
         Self {
+            inhibitor,
             count: Mutex::new(0),
+            display_count: Mutex::new(0),
             guard: Mutex::new(None),
             user_enabled: Mutex::new(false),
-            data_dir: Mutex::new(None),
+            user_display_enabled: Mutex::new(false),
+            settings: Mutex::new(None),
+            event_sink: Mutex::new(None),
+            changed: AtomicBool::new(false),
+            on_battery: AtomicBool::new(false),
+            keep_awake_on_battery: AtomicBool::new(false),
+            suppressed: AtomicBool::new(false),
+            suppressed_pending: AtomicBool::new(false),
+            timers: Mutex::new(HashMap::new()),
+            next_timer_id: AtomicU64::new(1),
+            holds: Mutex::new(Vec::new()),
+            schedule: Mutex::new((Vec::new(), false)),
+            schedule_expired: AtomicBool::new(false),
+            sidecar_watchdog: Mutex::new(None),
+            active_since: Mutex::new(None),
+            total_awake: Mutex::new(Duration::ZERO),
+            awake_unsaved: AtomicBool::new(false),
+            previous_awake: Mutex::new(Duration::ZERO),
+            max_duration: Mutex::new(Duration::from_secs(DEFAULT_MAX_WAKE_LOCK_MINUTES * 60)),
+            preferences: Mutex::new(()),
         }
     }
-}
 
-fn load_keep_awake(data_dir: &Path) -> bool {
-    let path = data_dir.join(SETTINGS_FILE);
-    std::fs::read_to_string(&path)
-        .ok()
-        .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
-        .and_then(|v| v.get("keep_awake")?.as_bool())
-        .unwrap_or(false)
-}
+    /// Initialize with the settings store. Restores saved preferences and acquires wake lock if needed.
+    pub fn init(&self, app: &AppHandle, store: &SettingsStore) {
+        let emitter = app.clone();
+        self.set_event_sink(Box::new(move |event, status| {
+            let _ = emitter.emit(event, status);
+        }));
+        *self.settings.lock().unwrap() = Some(store.clone());
+        let settings = store.settings().wake_lock;
+        *self.previous_awake.lock().unwrap() = Duration::from_secs(settings.total_awake_secs);
+        if let Some(minutes) = settings.max_wake_lock_minutes {
+            *self.max_duration.lock().unwrap() = Duration::from_secs(minutes * 60);
+        }
+        self.keep_awake_on_battery
+            .store(settings.keep_awake_on_battery, Ordering::Release);
+        // Check the power source before restoring, so we don't briefly hold
+        // a lock on battery
+        self.on_battery
+            .store(is_on_battery().unwrap_or(false), Ordering::Release);
+        if settings.keep_awake {
+            match self.increment() {
+                Ok(()) => {
+                    *self.user_enabled.lock().unwrap() = true;
+                    log::info!("[WakeLock] Restored keep-awake preference from previous session");
+                }
+                Err(e) => log::error!("[WakeLock] Failed to restore keep-awake preference: {}", e),
+            }
+        }
+        if settings.keep_display_awake {
+            match self.increment_mode(WakeLockMode::Display) {
+                Ok(()) => {
+                    *self.user_display_enabled.lock().unwrap() = true;
+                    log::info!(
+                        "[WakeLock] Restored keep-display-awake preference from previous session"
+                    );
+                }
+                Err(e) => log::error!(
+                    "[WakeLock] Failed to restore keep-display-awake preference: {}",
+                    e
+                ),
+            }
+        }
+        self.emit_if_changed();
+        self.start_power_monitor(app);
+
+        self.schedule.lock().unwrap().0 = parse_schedule(&settings.keep_awake_schedule);
+        self.evaluate_schedule();
+        self.start_schedule_monitor(app);
 
-fn save_keep_awake(data_dir: &Path, enabled: bool) {
-    let path = data_dir.join(SETTINGS_FILE);
-    // Read existing settings to preserve other fields
-    let mut settings = std::fs::read_to_string(&path)
-        .ok()
-        .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
-        .unwrap_or_else(|| serde_json::json!({}));
-    settings["keep_awake"] = serde_json::json!(enabled);
-    if let Err(e) = std::fs::write(&path, serde_json::to_string_pretty(&settings).unwrap()) {
-        log::warn!("[WakeLock] Failed to save preference: {}", e);
+        let app_handle = app.clone();
+        app.listen(settings::SETTINGS_CHANGED_EVENT, move |event| {
+            let Ok(changed) = serde_json::from_str::<SettingsChanged>(event.payload()) else {
+                return;
+            };
+            // Off the emitting thread, which may be saving one of our own
+            // preferences with the preferences lock held
+            let app_handle = app_handle.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                app_handle
+                    .state::<WakeLockState>()
+                    .settings_changed(&changed);
+            });
+        });
     }
-}
 
-impl WakeLockState {
-    /// Initialize with a data directory. Restores saved preference and acquires wake lock if needed.
-    pub fn init(&self, data_dir: &Path) {
-        *self.data_dir.lock().unwrap() = Some(data_dir.to_path_buf());
-        let saved = load_keep_awake(data_dir);
-        if saved {
-            *self.user_enabled.lock().unwrap() = true;
-            self.increment();
-            log::info!("[WakeLock] Restored keep-awake preference from previous session");
+    /// Re-apply preferences when `wake_lock` is written, e.g. by `set_setting`
+    /// from the frontend or an import
+    fn settings_changed(&self, changed: &SettingsChanged) {
+        if changed.keys.iter().any(|key| key == SETTINGS_KEY) {
+            self.reload_preferences();
         }
     }
 
+    /// Re-apply preferences from the settings file. Preferences that already
+    /// match what is applied are left alone.
+    fn reload_preferences(&self) {
+        let Some(store) = self.settings.lock().unwrap().clone() else {
+            return;
+        };
+        let _preferences = self.preferences.lock().unwrap();
+        let settings = store.settings().wake_lock;
+        let minutes = settings
+            .max_wake_lock_minutes
+            .unwrap_or(DEFAULT_MAX_WAKE_LOCK_MINUTES);
+        *self.max_duration.lock().unwrap() = Duration::from_secs(minutes * 60);
+        self.keep_awake_on_battery
+            .store(settings.keep_awake_on_battery, Ordering::Release);
+        self.resync();
+        for (mode, enabled) in [
+            (WakeLockMode::Idle, settings.keep_awake),
+            (WakeLockMode::Display, settings.keep_display_awake),
+        ] {
+            if let Err(e) = self.apply_user_mode(mode, enabled) {
+                log::error!("[WakeLock] Failed to apply {:?} preference: {}", mode, e);
+            }
+        }
+        self.schedule.lock().unwrap().0 = parse_schedule(&settings.keep_awake_schedule);
+        self.evaluate_schedule();
+        self.emit_if_changed();
+    }
+
     /// Whether the user has manually enabled keep-awake.
     pub fn is_user_enabled(&self) -> bool {
         *self.user_enabled.lock().unwrap()
     }
 
+    /// Whether the user has manually enabled keep-display-awake.
+    pub fn is_user_display_enabled(&self) -> bool {
+        *self.user_display_enabled.lock().unwrap()
+    }
+
+    /// Read the current state. Each lock is taken on its own and released
+    /// immediately, so this is safe to call while another lock is held elsewhere.
+    pub fn status(&self) -> WakeLockStatus {
+        let count = *self.count.lock().unwrap();
+        let display_count = *self.display_count.lock().unwrap();
+        let active = self.guard.lock().unwrap().is_some();
+        let active_since = *self.active_since.lock().unwrap();
+        let total_awake = *self.total_awake.lock().unwrap()
+            + active_since.map_or(Duration::ZERO, |(start, _)| start.elapsed());
+        let previous_awake = *self.previous_awake.lock().unwrap();
+        let (tags, reasons) = {
+            let holds = self.holds.lock().unwrap();
+            let mut tags = BTreeMap::new();
+            for hold in holds.iter() {
+                *tags.entry(hold.tag.clone()).or_insert(0) += 1;
+            }
+            let reasons = holds
+                .iter()
+                .filter_map(|hold| hold.reason.clone())
+                .collect();
+            (tags, reasons)
+        };
+        WakeLockStatus {
+            active,
+            count: count + display_count,
+            user_enabled: self.is_user_enabled(),
+            user_display_enabled: self.is_user_display_enabled(),
+            on_battery: self.on_battery.load(Ordering::Acquire),
+            suppressed: self.suppressed.load(Ordering::Acquire),
+            tags,
+            reasons,
+            total_awake_secs: total_awake.as_secs(),
+            lifetime_awake_secs: (previous_awake + total_awake).as_secs(),
+            currently_active_since: active_since.map(|(_, since)| {
+                since
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64
+            }),
+        }
+    }
+
+    /// Set whether the lock may be held while on battery, and persist it.
+    pub fn set_keep_awake_on_battery(&self, enabled: bool) {
+        let preferences = self.preferences.lock().unwrap();
+        self.keep_awake_on_battery.store(enabled, Ordering::Release);
+        self.save_settings(|settings| settings.wake_lock.keep_awake_on_battery = enabled);
+        drop(preferences);
+        self.resync();
+    }
+
+    /// Record the current power source, releasing the guard when unplugged
+    /// and restoring it when plugged back in.
+    fn set_on_battery(&self, on_battery: bool) {
+        if self.on_battery.swap(on_battery, Ordering::AcqRel) == on_battery {
+            return;
+        }
+        if on_battery {
+            log::info!("[WakeLock] Switched to battery power");
+        } else {
+            log::info!("[WakeLock] Switched to external power");
+        }
+        self.resync();
+    }
+
+    /// Re-evaluate the guard against the current counts and power policy.
+    fn resync(&self) {
+        let count = self.count.lock().unwrap();
+        let display_count = self.display_count.lock().unwrap();
+        if let Err(e) = self.sync_guard(*count, *display_count) {
+            log::error!("[WakeLock] Failed to restore after power change: {}", e);
+        }
+        drop(display_count);
+        drop(count);
+        self.emit_if_changed();
+    }
+
+    /// Poll the power source in the background so the guard follows plug/unplug,
+    /// re-acquire the guard after the system resumes from suspend, and enforce
+    /// the maximum hold duration.
+    ///
+    /// Resume is reported by the OS on macOS and Windows. Elsewhere it is
+    /// detected as a jump in wall-clock time between polls: the thread's
+    /// sleep doesn't advance while the machine is asleep, but the system
+    /// clock does.
+    fn start_power_monitor(&self, app: &AppHandle) {
+        let native_resume = watch_for_resume(app);
+        let app_handle = app.clone();
+        let spawned = std::thread::Builder::new()
+            .name("power-monitor".to_string())
+            .spawn(move || {
+                let mut last_poll = SystemTime::now();
+                loop {
+                    std::thread::sleep(POWER_POLL_INTERVAL);
+                    let now = SystemTime::now();
+                    let elapsed = now.duration_since(last_poll).unwrap_or_default();
+                    last_poll = now;
+
+                    let state: State<WakeLockState> = app_handle.state();
+                    if let Some(on_battery) = is_on_battery() {
+                        state.set_on_battery(on_battery);
+                    }
+                    if !native_resume && elapsed > POWER_POLL_INTERVAL + RESUME_GAP_THRESHOLD {
+                        state.system_resumed(Some(elapsed - POWER_POLL_INTERVAL));
+                    }
+                    state.enforce_max_duration();
+                }
+            });
+        if let Err(e) = spawned {
+            log::warn!("[WakeLock] Failed to start power monitor: {}", e);
+        }
+    }
+
+    /// Set the longest the guard may be held continuously, and persist it.
+    /// Zero disables the cap.
+    pub fn set_max_minutes(&self, minutes: u64) {
+        let _preferences = self.preferences.lock().unwrap();
+        *self.max_duration.lock().unwrap() = Duration::from_secs(minutes * 60);
+        self.save_settings(|settings| settings.wake_lock.max_wake_lock_minutes = Some(minutes));
+    }
+
+    /// Force-release everything if the guard has been held continuously past
+    /// the cap, in case whoever acquired it (e.g. a crashed frontend) never
+    /// releases. The user's keep-awake preferences are turned off and persisted.
+    pub fn enforce_max_duration(&self) {
+        let max_duration = *self.max_duration.lock().unwrap();
+        let held_for = self
+            .active_since
+            .lock()
+            .unwrap()
+            .map(|(start, _)| start.elapsed());
+        if max_duration.is_zero() || held_for.is_none_or(|held| held < max_duration) {
+            return;
+        }
+
+        log::warn!(
+            "[WakeLock] Held for over {} minutes, force releasing",
+            max_duration.as_secs() / 60
+        );
+        let preferences = self.preferences.lock().unwrap();
+        if self.schedule.lock().unwrap().1 {
+            self.schedule_expired.store(true, Ordering::Release);
+        }
+        self.release_all();
+        self.save_settings(|settings| {
+            settings.wake_lock.keep_awake = false;
+            settings.wake_lock.keep_display_awake = false;
+        });
+        drop(preferences);
+        self.emit(EXPIRED_EVENT);
+    }
+
     pub fn release_all(&self) {
+        // Cancel timers first so none fire against the reset count
+        for (_, timer) in self.timers.lock().unwrap().drain() {
+            timer.abort();
+        }
+        if let Some(watchdog) = self.sidecar_watchdog.lock().unwrap().take() {
+            watchdog.abort();
+        }
+        self.holds.lock().unwrap().clear();
+        self.schedule.lock().unwrap().1 = false;
         *self.count.lock().unwrap() = 0;
+        *self.display_count.lock().unwrap() = 0;
         *self.user_enabled.lock().unwrap() = false;
-        *self.guard.lock().unwrap() = None;
+        *self.user_display_enabled.lock().unwrap() = false;
+        self.suppressed.store(false, Ordering::Release);
+        if self.guard.lock().unwrap().take().is_some() {
+            self.changed.store(true, Ordering::Release);
+            self.end_awake_period();
+        }
+        self.emit_if_changed();
+    }
+
+    /// Turn on the user-requested wake lock. Does nothing if already on, so
+    /// repeated calls never add more than one reference.
+    /// If the lock can't be acquired, the preference is left off and not persisted.
+    pub fn enable_user_wake_lock(&self) -> Result<(), String> {
+        self.set_user_mode(WakeLockMode::Idle, true)
+    }
+
+    /// Turn off the user-requested wake lock. Does nothing if already off.
+    pub fn disable_user_wake_lock(&self) {
+        // Disabling never acquires, so it can't fail
+        let _ = self.set_user_mode(WakeLockMode::Idle, false);
     }
 
     /// Toggle user-requested wake lock. Returns the new checked state.
-    pub fn user_toggle(&self) -> bool {
-        let mut enabled = self.user_enabled.lock().unwrap();
-        if *enabled {
-            *enabled = false;
-            self.decrement();
+    pub fn user_toggle(&self) -> Result<bool, String> {
+        self.toggle_user_mode(WakeLockMode::Idle)
+    }
+
+    /// Toggle user-requested display wake lock. Returns the new checked state.
+    pub fn user_toggle_display(&self) -> Result<bool, String> {
+        self.toggle_user_mode(WakeLockMode::Display)
+    }
+
+    fn toggle_user_mode(&self, mode: WakeLockMode) -> Result<bool, String> {
+        let _preferences = self.preferences.lock().unwrap();
+        let enabled = match mode {
+            WakeLockMode::Idle => !self.is_user_enabled(),
+            WakeLockMode::Display => !self.is_user_display_enabled(),
+        };
+        if self.apply_user_mode(mode, enabled)? {
+            self.save_user_mode(mode, enabled);
+        }
+        Ok(enabled)
+    }
+
+    /// Set the user preference for `mode` and persist it, adding or removing
+    /// its reference only when the preference actually changes.
+    fn set_user_mode(&self, mode: WakeLockMode, enabled: bool) -> Result<(), String> {
+        let _preferences = self.preferences.lock().unwrap();
+        if self.apply_user_mode(mode, enabled)? {
+            self.save_user_mode(mode, enabled);
+        }
+        Ok(())
+    }
+
+    /// Apply the user preference for `mode` without persisting it. Returns
+    /// whether it changed.
+    fn apply_user_mode(&self, mode: WakeLockMode, enabled: bool) -> Result<bool, String> {
+        let flag = match mode {
+            WakeLockMode::Idle => &self.user_enabled,
+            WakeLockMode::Display => &self.user_display_enabled,
+        };
+        let mut current = flag.lock().unwrap();
+        if *current == enabled {
+            return Ok(false);
+        }
+        if enabled {
+            self.increment_mode(mode)?;
+        } else {
+            self.decrement_mode(mode);
+        }
+        *current = enabled;
+        drop(current);
+        self.emit_if_changed();
+        Ok(true)
+    }
+
+    fn save_user_mode(&self, mode: WakeLockMode, enabled: bool) {
+        self.save_settings(|settings| match mode {
+            WakeLockMode::Idle => settings.wake_lock.keep_awake = enabled,
+            WakeLockMode::Display => settings.wake_lock.keep_display_awake = enabled,
+        });
+    }
+
+    /// Replace the keep-awake schedule, persist it, and apply it immediately.
+    pub fn set_schedule(&self, windows: Vec<ScheduleWindow>) -> Result<(), String> {
+        let parsed = windows
+            .iter()
+            .map(ScheduleWindow::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        let preferences = self.preferences.lock().unwrap();
+        self.schedule.lock().unwrap().0 = parsed;
+        self.save_settings(|settings| settings.wake_lock.keep_awake_schedule = windows);
+        drop(preferences);
+        // A deliberately set schedule applies right away, even after the cap
+        self.schedule_expired.store(false, Ordering::Release);
+        self.evaluate_schedule();
+        Ok(())
+    }
+
+    /// Acquire or release the schedule's reference as windows open and close.
+    fn evaluate_schedule(&self) {
+        let now = chrono::Local::now();
+        self.apply_schedule(now.weekday(), now.hour() * 60 + now.minute());
+    }
+
+    /// Apply the schedule as of `minute` past midnight on `day`
+    fn apply_schedule(&self, day: Weekday, minute: u32) {
+        let mut schedule = self.schedule.lock().unwrap();
+        let (windows, held) = &mut *schedule;
+        let open = windows.iter().any(|w| w.contains(day, minute));
+        if !open {
+            self.schedule_expired.store(false, Ordering::Release);
+        } else if self.schedule_expired.load(Ordering::Acquire) {
+            return;
+        }
+        if open == *held {
+            return;
+        }
+        let result = if open {
+            self.acquire_tagged(SCHEDULE_TAG, Some("work hours"))
         } else {
-            *enabled = true;
-            self.increment();
+            self.release_tagged(SCHEDULE_TAG, None)
+        };
+        match result {
+            Ok(()) => {
+                *held = open;
+                if open {
+                    log::info!("[WakeLock] Schedule window opened");
+                } else {
+                    log::info!("[WakeLock] Schedule window closed");
+                }
+            }
+            Err(e) => log::error!("[WakeLock] Failed to apply schedule: {}", e),
+        }
+    }
+
+    /// Re-evaluate the schedule at the start of every minute.
+    fn start_schedule_monitor(&self, app: &AppHandle) {
+        let app_handle = app.clone();
+        let spawned = std::thread::Builder::new()
+            .name("wake-schedule".to_string())
+            .spawn(move || loop {
+                let second = chrono::Local::now().second().min(59);
+                std::thread::sleep(Duration::from_secs(u64::from(60 - second)));
+                let state: State<WakeLockState> = app_handle.state();
+                state.evaluate_schedule();
+            });
+        if let Err(e) = spawned {
+            log::warn!("[WakeLock] Failed to start schedule monitor: {}", e);
+        }
+    }
+
+    /// Acquire a reference on behalf of `tag`, optionally noting why.
+    fn acquire_tagged(&self, tag: &str, reason: Option<&str>) -> Result<(), String> {
+        let mut holds = self.holds.lock().unwrap();
+        self.increment()?;
+        holds.push(TaggedHold {
+            tag: tag.to_string(),
+            reason: reason.map(str::to_string),
+        });
+        log::debug!(
+            "[WakeLock] Acquired for '{}' ({})",
+            tag,
+            reason.unwrap_or("no reason")
+        );
+        drop(holds);
+        self.emit_if_changed();
+        Ok(())
+    }
+
+    /// Release one reference held by `tag`. If `reason` is given, the most
+    /// recent hold with that reason is released, otherwise the most recent
+    /// hold for the tag. Fails if there is no matching hold, so a subsystem
+    /// can't release references taken by another.
+    fn release_tagged(&self, tag: &str, reason: Option<&str>) -> Result<(), String> {
+        let mut holds = self.holds.lock().unwrap();
+        let position = holds.iter().rposition(|hold| {
+            hold.tag == tag && reason.is_none_or(|r| hold.reason.as_deref() == Some(r))
+        });
+        let Some(position) = position else {
+            return Err(format!("Wake lock tag '{}' is not held", tag));
+        };
+        holds.remove(position);
+        self.decrement();
+        log::debug!("[WakeLock] Released for '{}'", tag);
+        drop(holds);
+        self.emit_if_changed();
+        Ok(())
+    }
+
+    /// Release every reference held by `tag`. Returns how many were released.
+    fn clear_tag(&self, tag: &str) -> u32 {
+        let mut holds = self.holds.lock().unwrap();
+        let before = holds.len();
+        holds.retain(|hold| hold.tag != tag);
+        let released = (before - holds.len()) as u32;
+        for _ in 0..released {
+            self.decrement();
+        }
+        drop(holds);
+        self.emit_if_changed();
+        released
+    }
+
+    /// Hold a reference while the sidecar works on a request. Each start
+    /// re-arms the safety timeout.
+    fn sidecar_work_started(&self, app: &AppHandle) -> Result<(), String> {
+        self.acquire_tagged(SIDECAR_WORK_TAG, Some("generating response"))?;
+
+        let mut watchdog = self.sidecar_watchdog.lock().unwrap();
+        if let Some(previous) = watchdog.take() {
+            previous.abort();
+        }
+        let app_handle = app.clone();
+        *watchdog = Some(tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(SIDECAR_WORK_TIMEOUT).await;
+            let state: State<WakeLockState> = app_handle.state();
+            let released = state.clear_tag(SIDECAR_WORK_TAG);
+            if released > 0 {
+                log::warn!(
+                    "[WakeLock] Sidecar work timed out, released {} stale reference(s)",
+                    released
+                );
+            }
+        }));
+        Ok(())
+    }
+
+    /// Release a reference taken by `sidecar_work_started`. Completions that
+    /// arrive after the safety timeout already released everything are ignored.
+    fn sidecar_work_finished(&self) {
+        if self.release_tagged(SIDECAR_WORK_TAG, None).is_err() {
+            log::debug!("[WakeLock] Sidecar work finished with no reference held");
+        }
+        let idle = !self
+            .holds
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|hold| hold.tag == SIDECAR_WORK_TAG);
+        if idle {
+            if let Some(watchdog) = self.sidecar_watchdog.lock().unwrap().take() {
+                watchdog.abort();
+            }
+        }
+    }
+
+    /// Hold a wake lock for `duration`, releasing it automatically afterwards.
+    /// Returns the timer id, which can be passed to `cancel_timer` to release early.
+    fn acquire_for(&self, app: &AppHandle, duration: Duration) -> Result<u64, String> {
+        self.increment()?;
+        self.emit_if_changed();
+        let id = self.next_timer_id.fetch_add(1, Ordering::Relaxed);
+
+        // Hold the map lock across spawn + insert so a very short timer can't
+        // fire before its entry exists
+        let mut timers = self.timers.lock().unwrap();
+        let app_handle = app.clone();
+        let timer = tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(duration).await;
+            let state: State<WakeLockState> = app_handle.state();
+            // Whoever removes the entry owns the release, so a concurrent cancel
+            // can't decrement twice
+            if state.timers.lock().unwrap().remove(&id).is_some() {
+                log::info!("[WakeLock] Timer {} expired", id);
+                state.decrement();
+                state.emit_if_changed();
+            }
+        });
+        timers.insert(id, timer);
+        drop(timers);
+        log::info!(
+            "[WakeLock] Timer {} started for {}s",
+            id,
+            duration.as_secs()
+        );
+        Ok(id)
+    }
+
+    /// Release a timed wake lock before it expires. Returns false if the timer
+    /// already fired or was never started.
+    fn cancel_timer(&self, id: u64) -> bool {
+        let Some(timer) = self.timers.lock().unwrap().remove(&id) else {
+            return false;
+        };
+        timer.abort();
+        self.decrement();
+        self.emit_if_changed();
+        log::info!("[WakeLock] Timer {} cancelled", id);
+        true
+    }
+
+    /// Catch up after the system woke from sleep, having been asleep for
+    /// about `asleep` if known: the power source may have changed meanwhile,
+    /// and the guard may not have survived
+    fn system_resumed(&self, asleep: Option<Duration>) {
+        match asleep {
+            Some(asleep) => {
+                log::info!(
+                    "[WakeLock] System resumed after ~{}s asleep",
+                    asleep.as_secs()
+                )
+            }
+            None => log::info!("[WakeLock] System resumed from sleep"),
+        }
+        if let Some(on_battery) = is_on_battery() {
+            self.set_on_battery(on_battery);
+        }
+        self.reacquire();
+    }
+
+    /// Drop and recreate the held guard, since the OS may have invalidated it
+    /// across a suspend/resume. Does nothing when no guard is held.
+    pub fn reacquire(&self) {
+        let mut guard = self.guard.lock().unwrap();
+        let Some(display) = guard.as_ref().map(|g| g.display) else {
+            return;
+        };
+        *guard = None;
+        match self.inhibitor.acquire(display, true) {
+            Ok(inner) => {
+                *guard = Some(ActiveGuard {
+                    _inner: inner,
+                    display,
+                });
+                log::info!("[WakeLock] Re-acquired after resume");
+            }
+            Err(e) => {
+                log::error!("[WakeLock] Failed to re-acquire after resume: {}", e);
+                self.changed.store(true, Ordering::Release);
+                self.end_awake_period();
+            }
+        }
+        drop(guard);
+        self.emit_if_changed();
+    }
+
+    /// Fold the current hold into the session total. Called with the guard
+    /// locks held, so the lifetime total is left for `emit_if_changed` to save.
+    fn end_awake_period(&self) {
+        let Some((start, _)) = self.active_since.lock().unwrap().take() else {
+            return;
+        };
+        *self.total_awake.lock().unwrap() += start.elapsed();
+        self.awake_unsaved.store(true, Ordering::Release);
+    }
+
+    /// Persist the lifetime total if a hold ended since it was last saved.
+    /// Saving notifies settings listeners synchronously, so this never runs
+    /// under the guard locks.
+    fn save_awake_total(&self) {
+        if !self.awake_unsaved.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        let lifetime = *self.previous_awake.lock().unwrap() + *self.total_awake.lock().unwrap();
+        self.save_settings(|settings| settings.wake_lock.total_awake_secs = lifetime.as_secs());
+    }
+
+    /// Emit `wake-lock-changed` if the guard was created or dropped since the
+    /// last emit, saving the awake total first. Only called once every other
+    /// lock has been released, since the payload reads the full status.
+    fn emit_if_changed(&self) {
+        self.save_awake_total();
+        let changed = self.changed.swap(false, Ordering::AcqRel);
+        let suppressed = self.suppressed_pending.swap(false, Ordering::AcqRel);
+        if !changed && !suppressed {
+            return;
+        }
+        if changed {
+            self.emit(CHANGED_EVENT);
         }
-        let new_state = *enabled;
-        drop(enabled);
-        if let Some(dir) = self.data_dir.lock().unwrap().as_ref() {
-            save_keep_awake(dir, new_state);
+        if suppressed {
+            self.emit(SUPPRESSED_EVENT);
         }
-        new_state
     }
 
-    fn increment(&self) {
+    fn emit(&self, event: &str) {
+        let status = self.status();
+        if let Some(sink) = self.event_sink.lock().unwrap().as_ref() {
+            sink(event, &status);
+        }
+    }
+
+    /// Persist a change to the settings, if a settings store is attached
+    fn save_settings(&self, update: impl FnOnce(&mut Settings)) {
+        if let Some(store) = self.settings.lock().unwrap().as_ref() {
+            if let Err(e) = store.update(update) {
+                log::warn!("[WakeLock] Failed to save preference: {}", e);
+            }
+        }
+    }
+
+    fn set_event_sink(&self, sink: EventSink) {
+        *self.event_sink.lock().unwrap() = Some(sink);
+    }
+
+    fn increment(&self) -> Result<(), String> {
+        self.increment_mode(WakeLockMode::Idle)
+    }
+
+    fn decrement(&self) {
+        self.decrement_mode(WakeLockMode::Idle);
+    }
+
+    /// Add a reference, creating the guard if needed. The count is left
+    /// unchanged if the guard can't be created.
+    fn increment_mode(&self, mode: WakeLockMode) -> Result<(), String> {
         let mut count = self.count.lock().unwrap();
-        if *count == 0 {
-            match keepawake::Builder::default()
-                .display(false)
-                .idle(true)
-                .create()
-            {
-                Ok(guard) => {
-                    *self.guard.lock().unwrap() = Some(guard);
+        let mut display_count = self.display_count.lock().unwrap();
+        let (new_count, new_display_count) = match mode {
+            WakeLockMode::Idle => (*count + 1, *display_count),
+            WakeLockMode::Display => (*count, *display_count + 1),
+        };
+        if let Err(e) = self.sync_guard(new_count, new_display_count) {
+            log::error!("[WakeLock] Failed to acquire: {}", e);
+            return Err(format!("Couldn't prevent sleep on this platform: {}", e));
+        }
+        *count = new_count;
+        *display_count = new_display_count;
+        log::debug!(
+            "[WakeLock] Active count: {} (display: {})",
+            *count,
+            *display_count
+        );
+        Ok(())
+    }
+
+    fn decrement_mode(&self, mode: WakeLockMode) {
+        let mut count = self.count.lock().unwrap();
+        let mut display_count = self.display_count.lock().unwrap();
+        match mode {
+            WakeLockMode::Idle => *count = count.saturating_sub(1),
+            WakeLockMode::Display => *display_count = display_count.saturating_sub(1),
+        }
+        if let Err(e) = self.sync_guard(*count, *display_count) {
+            // Only reachable when downgrading to an idle-only guard. Keep the
+            // old guard rather than dropping the lock the idle holders still need.
+            log::error!("[WakeLock] Failed to rebuild guard: {}", e);
+        }
+        log::debug!(
+            "[WakeLock] Active count: {} (display: {})",
+            *count,
+            *display_count
+        );
+    }
+
+    /// Bring the held guard in line with the given reference counts.
+    ///
+    /// The guard is rebuilt whenever the display flag it needs changes, so
+    /// enabling display-awake while idle-awake is already held doesn't leave
+    /// a stale idle-only guard in place. The old guard is only dropped once
+    /// its replacement has been created.
+    ///
+    /// On battery (unless allowed by `keep_awake_on_battery`) no guard is held,
+    /// but the counts are kept so the lock comes back when plugged in.
+    fn sync_guard(&self, count: u32, display_count: u32) -> Result<(), String> {
+        let mut guard = self.guard.lock().unwrap();
+        let needed = count + display_count > 0;
+        let suppressed = needed
+            && self.on_battery.load(Ordering::Acquire)
+            && !self.keep_awake_on_battery.load(Ordering::Acquire);
+        if !self.suppressed.swap(suppressed, Ordering::AcqRel) && suppressed {
+            log::info!("[WakeLock] Suppressed while on battery power");
+            self.suppressed_pending.store(true, Ordering::Release);
+        }
+        let wanted = (needed && !suppressed).then_some(display_count > 0);
+        if guard.as_ref().map(|g| g.display) == wanted {
+            return Ok(());
+        }
+        // Rebuilding an already-held guard with new flags isn't a transition
+        let transition = guard.is_some() != wanted.is_some();
+
+        match wanted {
+            Some(display) => {
+                let inner = self.inhibitor.acquire(display, true)?;
+                *guard = Some(ActiveGuard {
+                    _inner: inner,
+                    display,
+                });
+                if display {
+                    log::info!("[WakeLock] Acquired (preventing idle and display sleep)");
+                } else {
                     log::info!("[WakeLock] Acquired (preventing idle sleep)");
                 }
-                Err(e) => {
-                    log::error!("[WakeLock] Failed to acquire: {}", e);
-                    return;
-                }
+            }
+            None => {
+                *guard = None;
+                log::info!("[WakeLock] Released (idle sleep re-enabled)");
             }
         }
-        *count += 1;
-        log::debug!("[WakeLock] Active count: {}", *count);
+        if transition {
+            self.changed.store(true, Ordering::Release);
+            if guard.is_some() {
+                *self.active_since.lock().unwrap() = Some((Instant::now(), SystemTime::now()));
+            } else {
+                self.end_awake_period();
+            }
+        }
+        Ok(())
     }
+}
 
-    fn decrement(&self) {
-        let mut count = self.count.lock().unwrap();
-        *count = count.saturating_sub(1);
-        if *count == 0 {
-            *self.guard.lock().unwrap() = None;
-            log::info!("[WakeLock] Released (idle sleep re-enabled)");
+/// Whether the machine is currently running on battery. `None` if the power
+/// source couldn't be read. Machines without a battery are never on battery.
+fn is_on_battery() -> Option<bool> {
+    let manager = starship_battery::Manager::new().ok()?;
+    let batteries = manager.batteries().ok()?;
+    for battery in batteries.flatten() {
+        if battery.state() == starship_battery::State::Discharging {
+            return Some(true);
         }
-        log::debug!("[WakeLock] Active count: {}", *count);
     }
+    Some(false)
+}
+
+/// Call `system_resumed` whenever the system wakes from sleep, through
+/// `NSWorkspaceDidWakeNotification`. Returns whether the hook is installed.
+#[cfg(target_os = "macos")]
+fn watch_for_resume(app: &AppHandle) -> bool {
+    use block2::RcBlock;
+    use objc2_app_kit::{NSWorkspace, NSWorkspaceDidWakeNotification};
+    use objc2_foundation::NSNotification;
+    use std::ptr::NonNull;
+
+    let app = app.clone();
+    let block = RcBlock::new(move |_: NonNull<NSNotification>| {
+        app.state::<WakeLockState>().system_resumed(None);
+    });
+    let center = NSWorkspace::sharedWorkspace().notificationCenter();
+    // SAFETY: the notification name is an AppKit constant, and the block
+    // only reaches managed state, which is safe to use from any thread
+    let observer = unsafe {
+        center.addObserverForName_object_queue_usingBlock(
+            Some(NSWorkspaceDidWakeNotification),
+            None,
+            None,
+            &block,
+        )
+    };
+    // Observed for the life of the app
+    std::mem::forget(observer);
+    true
+}
+
+/// Call `system_resumed` whenever the system wakes from sleep, on the
+/// `PBT_APMRESUMEAUTOMATIC` event `WM_POWERBROADCAST` carries. A callback
+/// registration delivers it without a window of our own. Returns whether
+/// the hook is installed.
+#[cfg(windows)]
+fn watch_for_resume(app: &AppHandle) -> bool {
+    use std::ffi::c_void;
+    use windows_sys::Win32::Foundation::ERROR_SUCCESS;
+    use windows_sys::Win32::System::Power::{
+        PowerRegisterSuspendResumeNotification, DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        DEVICE_NOTIFY_CALLBACK, PBT_APMRESUMEAUTOMATIC,
+    };
+
+    unsafe extern "system" fn on_power_event(
+        context: *const c_void,
+        event: u32,
+        _setting: *const c_void,
+    ) -> u32 {
+        if event == PBT_APMRESUMEAUTOMATIC {
+            // SAFETY: `context` is the `AppHandle` leaked at registration
+            let app = unsafe { &*(context as *const AppHandle) };
+            app.state::<WakeLockState>().system_resumed(None);
+        }
+        ERROR_SUCCESS
+    }
+
+    // Registered for the life of the app, so neither is ever freed
+    let parameters = Box::leak(Box::new(DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS {
+        Callback: Some(on_power_event),
+        Context: Box::into_raw(Box::new(app.clone())) as *mut c_void,
+    }));
+    let mut registration = std::ptr::null_mut();
+    // SAFETY: `parameters` and the handle it points to are never freed
+    let result = unsafe {
+        PowerRegisterSuspendResumeNotification(
+            DEVICE_NOTIFY_CALLBACK,
+            parameters as *mut DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS as *mut c_void,
+            &mut registration,
+        )
+    };
+    if result != ERROR_SUCCESS {
+        log::warn!(
+            "[WakeLock] Failed to register for resume events (error {})",
+            result
+        );
+        return false;
+    }
+    true
+}
+
+/// No native wake notifications here; the power monitor watches the clock
+#[cfg(not(any(target_os = "macos", windows)))]
+fn watch_for_resume(_app: &AppHandle) -> bool {
+    false
 }
 
 #[tauri::command]
 pub fn acquire_wake_lock(state: State<'_, WakeLockState>) -> Result<(), String> {
-    state.increment();
+    state.increment()?;
+    state.emit_if_changed();
     Ok(())
 }
 
 #[tauri::command]
 pub fn release_wake_lock(state: State<'_, WakeLockState>) -> Result<(), String> {
     state.decrement();
+    state.emit_if_changed();
     Ok(())
 }
+
+#[tauri::command]
+pub fn get_wake_lock_status(state: State<'_, WakeLockState>) -> WakeLockStatus {
+    state.status()
+}
+
+/// Acquire a wake lock on behalf of a named subsystem, with an optional
+/// human-readable reason
+#[tauri::command]
+pub fn acquire_wake_lock_tagged(
+    state: State<'_, WakeLockState>,
+    tag: String,
+    reason: Option<String>,
+) -> Result<(), String> {
+    state.acquire_tagged(&tag, reason.as_deref())
+}
+
+/// Release a wake lock previously acquired with the same tag (and reason, if given)
+#[tauri::command]
+pub fn release_wake_lock_tagged(
+    state: State<'_, WakeLockState>,
+    tag: String,
+    reason: Option<String>,
+) -> Result<(), String> {
+    state.release_tagged(&tag, reason.as_deref())
+}
+
+/// Keep the system awake while the sidecar works on a request
+#[tauri::command]
+pub fn sidecar_work_started(app: AppHandle, state: State<'_, WakeLockState>) -> Result<(), String> {
+    state.sidecar_work_started(&app)
+}
+
+/// Release the wake lock taken by `sidecar_work_started`
+#[tauri::command]
+pub fn sidecar_work_finished(state: State<'_, WakeLockState>) {
+    state.sidecar_work_finished();
+}
+
+/// Set the longest the wake lock may be held continuously (0 disables the cap)
+#[tauri::command]
+pub fn set_max_wake_lock_minutes(state: State<'_, WakeLockState>, minutes: u64) {
+    state.set_max_minutes(minutes);
+}
+
+/// Replace the keep-awake schedule
+#[tauri::command]
+pub fn set_keep_awake_schedule(
+    state: State<'_, WakeLockState>,
+    schedule: Vec<ScheduleWindow>,
+) -> Result<(), String> {
+    state.set_schedule(schedule)
+}
+
+/// Turn the user's keep-awake preference on or off. Safe to call repeatedly.
+#[tauri::command]
+pub fn set_keep_awake(state: State<'_, WakeLockState>, enabled: bool) -> Result<(), String> {
+    if enabled {
+        state.enable_user_wake_lock()
+    } else {
+        state.disable_user_wake_lock();
+        Ok(())
+    }
+}
+
+/// Allow or prevent the wake lock from being held while on battery power
+#[tauri::command]
+pub fn set_keep_awake_on_battery(state: State<'_, WakeLockState>, enabled: bool) {
+    state.set_keep_awake_on_battery(enabled);
+}
+
+/// Keep the system awake for the next `seconds`. Returns a timer id that can be
+/// passed to `release_wake_lock_timer` to release early.
+#[tauri::command]
+pub fn acquire_wake_lock_for(
+    app: AppHandle,
+    state: State<'_, WakeLockState>,
+    seconds: u64,
+) -> Result<u64, String> {
+    state.acquire_for(&app, Duration::from_secs(seconds))
+}
+
+#[tauri::command]
+pub fn release_wake_lock_timer(state: State<'_, WakeLockState>, id: u64) -> Result<(), String> {
+    if state.cancel_timer(id) {
+        Ok(())
+    } else {
+        Err(format!("Wake lock timer {} is not active", id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_dir;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    /// Tracks how many guards are alive and the flags of the latest one
+    #[derive(Clone, Default)]
+    struct MockInhibitor {
+        live: Arc<AtomicUsize>,
+        last_display: Arc<AtomicBool>,
+    }
+
+    struct MockGuard {
+        live: Arc<AtomicUsize>,
+    }
+
+    impl Guard for MockGuard {}
+
+    impl Drop for MockGuard {
+        fn drop(&mut self) {
+            self.live.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    impl SleepInhibitor for MockInhibitor {
+        fn acquire(&self, display: bool, _idle: bool) -> Result<Box<dyn Guard>, String> {
+            self.live.fetch_add(1, Ordering::SeqCst);
+            self.last_display.store(display, Ordering::SeqCst);
+            Ok(Box::new(MockGuard {
+                live: self.live.clone(),
+            }))
+        }
+    }
+
+    impl MockInhibitor {
+        fn live(&self) -> usize {
+            self.live.load(Ordering::SeqCst)
+        }
+
+        fn last_display(&self) -> bool {
+            self.last_display.load(Ordering::SeqCst)
+        }
+    }
+
+    fn mock_state() -> (WakeLockState, MockInhibitor) {
+        let inhibitor = MockInhibitor::default();
+        (
+            WakeLockState::with_inhibitor(Box::new(inhibitor.clone())),
+            inhibitor,
+        )
+    }
+
+    struct FailingInhibitor;
+
+    impl SleepInhibitor for FailingInhibitor {
+        fn acquire(&self, _display: bool, _idle: bool) -> Result<Box<dyn Guard>, String> {
+            Err("unsupported".to_string())
+        }
+    }
+
+    #[test]
+    fn failed_acquire_leaves_state_untouched() {
+        let state = WakeLockState::with_inhibitor(Box::new(FailingInhibitor));
+
+        assert!(state.increment().is_err());
+        assert!(state.user_toggle().is_err());
+        assert!(state.user_toggle_display().is_err());
+
+        let status = state.status();
+        assert!(!status.active);
+        assert_eq!(status.count, 0);
+        assert!(!status.user_enabled);
+        assert!(!status.user_display_enabled);
+    }
+
+    #[test]
+    fn guard_lives_while_references_are_held() {
+        let (state, inhibitor) = mock_state();
+
+        state.increment().unwrap();
+        state.increment().unwrap();
+        assert_eq!(inhibitor.live(), 1);
+        assert_eq!(state.status().count, 2);
+
+        state.decrement();
+        assert_eq!(inhibitor.live(), 1);
+        state.decrement();
+        assert_eq!(inhibitor.live(), 0);
+        assert!(!state.status().active);
+
+        // Extra releases don't underflow
+        state.decrement();
+        assert_eq!(state.status().count, 0);
+    }
+
+    #[test]
+    fn user_toggle_adds_and_removes_one_reference() {
+        let (state, inhibitor) = mock_state();
+        state.increment().unwrap();
+
+        assert!(state.user_toggle().unwrap());
+        assert_eq!(state.status().count, 2);
+        assert!(!state.user_toggle().unwrap());
+        assert_eq!(state.status().count, 1);
+        assert_eq!(inhibitor.live(), 1);
+    }
+
+    #[test]
+    fn display_mode_rebuilds_guard_with_combined_flags() {
+        let (state, inhibitor) = mock_state();
+
+        state.user_toggle().unwrap();
+        assert!(!inhibitor.last_display());
+
+        state.user_toggle_display().unwrap();
+        assert!(inhibitor.last_display());
+        assert_eq!(inhibitor.live(), 1);
+
+        state.user_toggle_display().unwrap();
+        assert!(!inhibitor.last_display());
+        assert_eq!(inhibitor.live(), 1);
+
+        state.user_toggle().unwrap();
+        assert_eq!(inhibitor.live(), 0);
+    }
+
+    #[test]
+    fn release_all_drops_everything() {
+        let (state, inhibitor) = mock_state();
+        state.increment().unwrap();
+        state.user_toggle().unwrap();
+        state.user_toggle_display().unwrap();
+        state
+            .acquire_tagged("download", Some("fetching model"))
+            .unwrap();
+
+        state.release_all();
+
+        let status = state.status();
+        assert_eq!(inhibitor.live(), 0);
+        assert_eq!(status.count, 0);
+        assert!(!status.user_enabled);
+        assert!(!status.user_display_enabled);
+        assert!(status.tags.is_empty());
+        assert!(status.reasons.is_empty());
+    }
+
+    #[test]
+    fn max_duration_force_releases() {
+        let (state, _inhibitor) = mock_state();
+        *state.max_duration.lock().unwrap() = Duration::from_millis(10);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink_events = events.clone();
+        state.set_event_sink(Box::new(move |event, _| {
+            sink_events.lock().unwrap().push(event.to_string());
+        }));
+
+        state.user_toggle().unwrap();
+        assert!(state.status().active);
+
+        std::thread::sleep(Duration::from_millis(20));
+        state.enforce_max_duration();
+
+        let status = state.status();
+        assert!(!status.active);
+        assert_eq!(status.count, 0);
+        assert!(!status.user_enabled);
+        assert!(events.lock().unwrap().iter().any(|e| e == EXPIRED_EVENT));
+
+        // The cap starts fresh on the next acquisition
+        state.user_toggle().unwrap();
+        state.enforce_max_duration();
+        assert!(state.status().active);
+    }
+
+    #[test]
+    fn max_duration_keeps_schedule_off_until_its_window_closes() {
+        let (state, inhibitor) = mock_state();
+        *state.max_duration.lock().unwrap() = Duration::from_millis(10);
+        let window = ScheduleWindow {
+            start: "09:00".to_string(),
+            end: "17:00".to_string(),
+            days: Vec::new(),
+        };
+        state.schedule.lock().unwrap().0 = vec![window.parse().unwrap()];
+
+        state.apply_schedule(Weekday::Mon, 9 * 60);
+        assert_eq!(inhibitor.live(), 1);
+        std::thread::sleep(Duration::from_millis(20));
+        state.enforce_max_duration();
+        assert_eq!(inhibitor.live(), 0);
+
+        // Later in the same window the schedule doesn't take the lock back
+        state.apply_schedule(Weekday::Mon, 9 * 60 + 1);
+        state.apply_schedule(Weekday::Mon, 13 * 60);
+        assert_eq!(inhibitor.live(), 0);
+
+        // Once it closes, the next window applies as usual
+        state.apply_schedule(Weekday::Mon, 17 * 60);
+        state.apply_schedule(Weekday::Tue, 9 * 60);
+        assert_eq!(inhibitor.live(), 1);
+        assert!(state.status().tags.contains_key(SCHEDULE_TAG));
+    }
+
+    #[test]
+    fn awake_total_is_saved_once_a_hold_ends() {
+        let dir = test_dir("wake-total");
+        let store = SettingsStore::load(&dir);
+        let (state, _inhibitor) = mock_state();
+        *state.settings.lock().unwrap() = Some(store.clone());
+        *state.previous_awake.lock().unwrap() = Duration::from_secs(90);
+
+        state.user_toggle().unwrap();
+        assert_eq!(
+            store.get_ns(SETTINGS_KEY, "total_awake_secs"),
+            Some(serde_json::json!(0))
+        );
+        state.user_toggle().unwrap();
+        assert_eq!(
+            store.get_ns(SETTINGS_KEY, "total_awake_secs"),
+            Some(serde_json::json!(90))
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn external_wake_lock_writes_are_applied() {
+        let dir = test_dir("wake-settings");
+        let store = SettingsStore::load(&dir);
+        let (state, inhibitor) = mock_state();
+        *state.settings.lock().unwrap() = Some(store.clone());
+        let changed = |key: &str| SettingsChanged {
+            keys: vec![key.to_string()],
+            key: Some(key.to_string()),
+            value: None,
+        };
+
+        store
+            .set_ns(SETTINGS_KEY, "keep_awake", serde_json::json!(true))
+            .unwrap();
+        state.settings_changed(&changed("theme"));
+        assert!(!state.is_user_enabled());
+        state.settings_changed(&changed(SETTINGS_KEY));
+        assert!(state.is_user_enabled());
+        assert_eq!(inhibitor.live(), 1);
+
+        // A toggle is saved along with the state change, so a reload agrees
+        state.user_toggle().unwrap();
+        assert_eq!(
+            store.get_ns(SETTINGS_KEY, "keep_awake"),
+            Some(serde_json::json!(false))
+        );
+        state.settings_changed(&changed(SETTINGS_KEY));
+        assert!(!state.is_user_enabled());
+        assert_eq!(inhibitor.live(), 0);
+
+        let always = serde_json::json!([{ "start": "00:00", "end": "23:59" }]);
+        store
+            .set_ns(SETTINGS_KEY, "keep_awake_schedule", always)
+            .unwrap();
+        state.settings_changed(&changed(SETTINGS_KEY));
+        assert_eq!(state.schedule.lock().unwrap().0.len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn user_enable_and_disable_are_idempotent() {
+        let (state, inhibitor) = mock_state();
+        // Non-user acquirers that must survive any sequence of user calls
+        state.increment().unwrap();
+        state.increment().unwrap();
+        state.acquire_tagged("download", None).unwrap();
+        let others = 3;
+
+        std::thread::scope(|scope| {
+            for seed in 1..=4u64 {
+                let state = &state;
+                scope.spawn(move || {
+                    // xorshift, so each thread runs its own random sequence
+                    let mut x = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+                    for _ in 0..500 {
+                        x ^= x << 13;
+                        x ^= x >> 7;
+                        x ^= x << 17;
+                        match x % 3 {
+                            0 => state.enable_user_wake_lock().unwrap(),
+                            1 => state.disable_user_wake_lock(),
+                            _ => {
+                                state.user_toggle().unwrap();
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        let status = state.status();
+        let expected = others + u32::from(status.user_enabled);
+        assert_eq!(status.count, expected);
+
+        state.disable_user_wake_lock();
+        state.disable_user_wake_lock();
+        assert_eq!(state.status().count, others);
+        state.enable_user_wake_lock().unwrap();
+        state.enable_user_wake_lock().unwrap();
+        assert_eq!(state.status().count, others + 1);
+        assert_eq!(inhibitor.live(), 1);
+    }
+
+    #[test]
+    fn reasons_track_tagged_holds() {
+        let (state, _inhibitor) = mock_state();
+        state
+            .acquire_tagged("sidecar", Some("generating response"))
+            .unwrap();
+        state
+            .acquire_tagged("schedule", Some("work hours"))
+            .unwrap();
+        state
+            .acquire_tagged("sidecar", Some("generating response"))
+            .unwrap();
+        state.acquire_tagged("sidecar", None).unwrap();
+
+        let status = state.status();
+        assert_eq!(status.tags["sidecar"], 3);
+        assert_eq!(
+            status.reasons,
+            vec!["generating response", "work hours", "generating response"]
+        );
+
+        state
+            .release_tagged("sidecar", Some("generating response"))
+            .unwrap();
+        state.release_tagged("schedule", None).unwrap();
+        assert!(state.release_tagged("schedule", None).is_err());
+
+        let status = state.status();
+        assert_eq!(status.count, 2);
+        assert_eq!(status.reasons, vec!["generating response"]);
+    }
+}
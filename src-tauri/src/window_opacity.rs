@@ -0,0 +1,116 @@
+use tauri::{AppHandle, Manager, WebviewWindow};
+
+use crate::settings::SettingsStore;
+
+/// Range `set_window_opacity` clamps to, so the window can't become
+/// invisible and impossible to find
+const MIN_OPACITY: f64 = 0.2;
+const MAX_OPACITY: f64 = 1.0;
+
+/// The saved opacity of the main window, fully opaque unless changed
+pub fn opacity(store: &SettingsStore) -> f64 {
+    store
+        .settings()
+        .window_opacity
+        .filter(|opacity| opacity.is_finite())
+        .map_or(MAX_OPACITY, |opacity| {
+            opacity.clamp(MIN_OPACITY, MAX_OPACITY)
+        })
+}
+
+/// Apply the saved opacity at startup
+pub fn restore_window_opacity(app: &AppHandle, store: &SettingsStore) {
+    let opacity = opacity(store);
+    if opacity < MAX_OPACITY {
+        if let Some(window) = app.get_webview_window("main") {
+            apply_opacity(&window, opacity);
+        }
+    }
+}
+
+/// Set and save the main window's opacity, returning it clamped to 0.2-1.0.
+/// Together with always-on-top this makes a see-through overlay.
+pub fn set_window_opacity(
+    app: &AppHandle,
+    store: &SettingsStore,
+    opacity: f64,
+) -> Result<f64, String> {
+    if !opacity.is_finite() {
+        return Err(format!("Invalid opacity: {}", opacity));
+    }
+    let opacity = opacity.clamp(MIN_OPACITY, MAX_OPACITY);
+    store.update(|settings| settings.window_opacity = Some(opacity))?;
+    if let Some(window) = app.get_webview_window("main") {
+        apply_opacity(&window, opacity);
+    }
+    Ok(opacity)
+}
+
+/// Native window handles may only be touched from the main thread. Where
+/// per-window opacity isn't supported this only logs.
+fn apply_opacity(window: &WebviewWindow, opacity: f64) {
+    let target = window.clone();
+    let result = window.run_on_main_thread(move || match set_native_opacity(&target, opacity) {
+        Ok(()) => log::info!("[Window] Opacity set to {:.2}", opacity),
+        Err(e) => log::warn!("[Window] Couldn't set opacity: {}", e),
+    });
+    if let Err(e) = result {
+        log::warn!("[Window] Couldn't set opacity: {}", e);
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn set_native_opacity(window: &WebviewWindow, opacity: f64) -> Result<(), String> {
+    use objc2::msg_send;
+    use objc2::runtime::AnyObject;
+
+    let ns_window = window.ns_window().map_err(|e| e.to_string())? as *mut AnyObject;
+    // SAFETY: `ns_window` is the live NSWindow behind `window`, and we're on
+    // the main thread
+    unsafe {
+        let _: () = msg_send![ns_window, setAlphaValue: opacity];
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn set_native_opacity(window: &WebviewWindow, opacity: f64) -> Result<(), String> {
+    use windows_sys::Win32::Foundation::HWND;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GetWindowLongPtrW, SetLayeredWindowAttributes, SetWindowLongPtrW, GWL_EXSTYLE, LWA_ALPHA,
+        WS_EX_LAYERED,
+    };
+
+    let hwnd = window.hwnd().map_err(|e| e.to_string())?.0 as HWND;
+    let alpha = (opacity * 255.0).round() as u8;
+    // SAFETY: `hwnd` is the live top-level window behind `window`
+    unsafe {
+        let style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+        SetWindowLongPtrW(hwnd, GWL_EXSTYLE, style | WS_EX_LAYERED as isize);
+        if SetLayeredWindowAttributes(hwnd, 0, alpha, LWA_ALPHA) == 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_native_opacity(window: &WebviewWindow, opacity: f64) -> Result<(), String> {
+    use gtk::prelude::WidgetExt;
+
+    let gtk_window = window.gtk_window().map_err(|e| e.to_string())?;
+    // Without a compositor GTK silently ignores the opacity
+    if !gtk_window
+        .screen()
+        .is_some_and(|screen| screen.is_composited())
+    {
+        return Err("the window manager doesn't support transparency".to_string());
+    }
+    gtk_window.set_opacity(opacity);
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "macos", windows, target_os = "linux")))]
+fn set_native_opacity(_window: &WebviewWindow, _opacity: f64) -> Result<(), String> {
+    Err("not supported on this platform".to_string())
+}
@@ -0,0 +1,523 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{
+    AppHandle, Emitter, Manager, Monitor, PhysicalPosition, PhysicalSize, WebviewWindow,
+    WindowEvent,
+};
+
+use crate::settings::SettingsStore;
+
+const MAIN_WINDOW: &str = "main";
+
+/// Range `set_zoom` clamps the webview zoom factor to
+const MIN_ZOOM: f64 = 0.5;
+const MAX_ZOOM: f64 = 3.0;
+
+/// Emitted with the new value whenever always-on-top is toggled
+pub(crate) const ALWAYS_ON_TOP_EVENT: &str = "always-on-top-changed";
+
+/// Wait for moves and resizes to settle this long before saving, so dragging
+/// the window doesn't write settings on every frame
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Where the main window was left. Stored under `window_state` in settings.
+///
+/// With `monitor` set, all four are logical pixels and `x`/`y` are relative
+/// to that monitor's work area, so the window lands in the same spot on a
+/// display with a different scale factor. Without it (files saved before
+/// monitors were remembered) they're absolute physical pixels.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WindowState {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub maximized: bool,
+    /// The window was fullscreen; the geometry is what it returns to
+    #[serde(default)]
+    pub fullscreen: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<MonitorId>,
+}
+
+/// Identifies a monitor across launches: by name where the platform reports
+/// one, otherwise by where it sits in the desktop
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MonitorId {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl MonitorId {
+    fn of(monitor: &Monitor) -> Self {
+        Self {
+            name: monitor.name().cloned(),
+            x: monitor.position().x,
+            y: monitor.position().y,
+        }
+    }
+
+    fn matches(&self, other: &MonitorId) -> bool {
+        match (&self.name, &other.name) {
+            (Some(name), Some(other_name)) => name == other_name,
+            _ => (self.x, self.y) == (other.x, other.y),
+        }
+    }
+}
+
+/// A monitor's work area in physical pixels
+#[derive(Clone, Copy, Debug)]
+struct Area {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+impl Area {
+    /// Pixels of `state` that fall inside this area
+    fn overlap(&self, state: &WindowState) -> u64 {
+        let span = |start: i32, len: u32, other_start: i32, other_len: u32| {
+            let end = |start: i32, len: u32| i64::from(start) + i64::from(len);
+            let overlap = end(start, len).min(end(other_start, other_len))
+                - i64::from(start.max(other_start));
+            overlap.max(0) as u64
+        };
+        span(self.x, self.width, state.x, state.width)
+            * span(self.y, self.height, state.y, state.height)
+    }
+
+    /// `state` moved and shrunk to fit inside this area
+    fn clamp(&self, state: &WindowState) -> WindowState {
+        let width = state.width.min(self.width);
+        let height = state.height.min(self.height);
+        let max_x = self.x.saturating_add((self.width - width) as i32);
+        let max_y = self.y.saturating_add((self.height - height) as i32);
+        WindowState {
+            x: state.x.clamp(self.x, max_x),
+            y: state.y.clamp(self.y, max_y),
+            width,
+            height,
+            maximized: state.maximized,
+            fullscreen: state.fullscreen,
+            monitor: None,
+        }
+    }
+}
+
+/// A connected monitor, as far as placing the window is concerned
+#[derive(Clone, Debug)]
+struct Display {
+    id: MonitorId,
+    area: Area,
+    scale: f64,
+    primary: bool,
+}
+
+impl Display {
+    fn of(monitor: &Monitor, primary: Option<&Monitor>) -> Self {
+        let area = monitor.work_area();
+        let id = MonitorId::of(monitor);
+        Self {
+            primary: primary.is_some_and(|primary| MonitorId::of(primary) == id),
+            id,
+            area: Area {
+                x: area.position.x,
+                y: area.position.y,
+                width: area.size.width,
+                height: area.size.height,
+            },
+            scale: monitor.scale_factor(),
+        }
+    }
+}
+
+/// `state` moved and shrunk to fit inside the monitor it overlaps most, or
+/// `None` if it doesn't overlap any of them
+fn fit_to_monitors(state: &WindowState, monitors: &[Area]) -> Option<WindowState> {
+    let monitor = monitors
+        .iter()
+        .filter(|m| m.overlap(state) > 0)
+        .max_by_key(|m| m.overlap(state))?;
+    Some(monitor.clamp(state))
+}
+
+/// Where to put a saved window among the connected displays, in absolute
+/// physical pixels. A window saved on a monitor that's gone moves to the
+/// primary one.
+fn place_window(state: &WindowState, displays: &[Display]) -> Option<WindowState> {
+    let Some(id) = &state.monitor else {
+        let areas: Vec<Area> = displays.iter().map(|display| display.area).collect();
+        return fit_to_monitors(state, &areas);
+    };
+    let display = displays
+        .iter()
+        .find(|display| display.id.matches(id))
+        .or_else(|| displays.iter().find(|display| display.primary))
+        .or_else(|| displays.first())?;
+    let physical = |logical: i64| (logical as f64 * display.scale).round() as i64;
+    let physical = WindowState {
+        x: (i64::from(display.area.x) + physical(i64::from(state.x))) as i32,
+        y: (i64::from(display.area.y) + physical(i64::from(state.y))) as i32,
+        width: physical(i64::from(state.width)) as u32,
+        height: physical(i64::from(state.height)) as u32,
+        maximized: state.maximized,
+        fullscreen: state.fullscreen,
+        monitor: None,
+    };
+    Some(display.area.clamp(&physical))
+}
+
+/// Move the main window back to where it was left, on the same monitor when
+/// it's still connected. A window saved without a monitor that's now
+/// off-screen keeps the default centered placement.
+pub fn restore_window_state(app: &AppHandle, store: &SettingsStore) {
+    let Some(window) = app.get_webview_window(MAIN_WINDOW) else {
+        return;
+    };
+    let Some(saved) = store.settings().window_state else {
+        return;
+    };
+    let Some(state) = restore_geometry(&window, &saved) else {
+        log::info!("[Window] Saved position is off-screen, using the default");
+        return;
+    };
+    if state.fullscreen {
+        let _ = window.set_fullscreen(true);
+    } else if state.maximized {
+        let _ = window.maximize();
+    }
+    log::info!(
+        "[Window] Restored {}x{} at ({}, {})",
+        state.width,
+        state.height,
+        state.x,
+        state.y
+    );
+}
+
+/// Move and size `window` to the saved geometry, returning where it ended up
+fn restore_geometry(window: &WebviewWindow, saved: &WindowState) -> Option<WindowState> {
+    let primary = window.primary_monitor().ok().flatten();
+    let displays: Vec<Display> = window
+        .available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .map(|monitor| Display::of(monitor, primary.as_ref()))
+        .collect();
+
+    let state = place_window(saved, &displays)?;
+    // Move first so the size is applied at the target monitor's scale
+    let _ = window.set_position(PhysicalPosition::new(state.x, state.y));
+    let _ = window.set_size(PhysicalSize::new(state.width, state.height));
+    Some(state)
+}
+
+/// Enter or leave fullscreen, returning the new state. On macOS this uses
+/// native fullscreen, in its own Space. Leaving fullscreen puts the window
+/// back where it was, since geometry isn't saved while fullscreen.
+pub fn toggle_fullscreen(app: &AppHandle, store: &SettingsStore) -> Result<bool, String> {
+    let window = app
+        .get_webview_window(MAIN_WINDOW)
+        .ok_or("Main window not found")?;
+    let fullscreen = !window.is_fullscreen().unwrap_or(false);
+    window
+        .set_fullscreen(fullscreen)
+        .map_err(|e| format!("Failed to toggle fullscreen: {}", e))?;
+    // macOS animates back to the old frame itself, and resizing mid-animation
+    // fights it
+    if !fullscreen && !cfg!(target_os = "macos") {
+        if let Some(saved) = store
+            .settings()
+            .window_state
+            .filter(|saved| !saved.maximized)
+        {
+            restore_geometry(&window, &saved);
+        }
+    }
+    log::info!(
+        "[Window] Fullscreen {}",
+        if fullscreen { "entered" } else { "exited" }
+    );
+    Ok(fullscreen)
+}
+
+/// The saved webview zoom factor, 1.0 unless the user changed it
+pub fn zoom(store: &SettingsStore) -> f64 {
+    store
+        .settings()
+        .zoom
+        .filter(|factor| factor.is_finite())
+        .map_or(1.0, |factor| factor.clamp(MIN_ZOOM, MAX_ZOOM))
+}
+
+/// Apply the saved zoom factor to a newly created window
+pub fn apply_zoom(window: &WebviewWindow, store: &SettingsStore) {
+    let factor = zoom(store);
+    if factor != 1.0 {
+        let _ = window.set_zoom(factor);
+    }
+}
+
+/// Zoom every app window and save the factor, returning it clamped to
+/// 0.5-3.0
+pub fn set_zoom(app: &AppHandle, store: &SettingsStore, factor: f64) -> Result<f64, String> {
+    if !factor.is_finite() {
+        return Err(format!("Invalid zoom factor: {}", factor));
+    }
+    let factor = factor.clamp(MIN_ZOOM, MAX_ZOOM);
+    for (label, window) in app.webview_windows() {
+        if label != "splashscreen" {
+            window
+                .set_zoom(factor)
+                .map_err(|e| format!("Failed to zoom window '{}': {}", label, e))?;
+        }
+    }
+    store.update(|settings| settings.zoom = Some(factor))?;
+    Ok(factor)
+}
+
+/// Whether the user pinned the main window above other windows
+pub fn is_always_on_top(store: &SettingsStore) -> bool {
+    store.settings().always_on_top.unwrap_or(false)
+}
+
+/// Apply the saved always-on-top flag. A fullscreen window is never forced
+/// on top, so other apps can still come forward over it.
+fn apply_always_on_top(window: &WebviewWindow, store: &SettingsStore) {
+    let fullscreen = window.is_fullscreen().unwrap_or(false);
+    let _ = window.set_always_on_top(is_always_on_top(store) && !fullscreen);
+}
+
+/// Restore the always-on-top flag saved by a previous session
+pub fn restore_always_on_top(app: &AppHandle, store: &SettingsStore) {
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        apply_always_on_top(&window, store);
+    }
+}
+
+/// Flip always-on-top for the main window, returning the new state
+pub fn toggle_always_on_top(app: &AppHandle, store: &SettingsStore) -> Result<bool, String> {
+    let enabled = !is_always_on_top(store);
+    store.update(|settings| settings.always_on_top = Some(enabled))?;
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        apply_always_on_top(&window, store);
+    }
+    log::info!(
+        "[Window] Always on top {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    let _ = app.emit(ALWAYS_ON_TOP_EVENT, enabled);
+    Ok(enabled)
+}
+
+/// Save the main window's geometry whenever it's moved or resized
+pub fn track_window_state(app: &AppHandle, store: &SettingsStore) {
+    let Some(window) = app.get_webview_window(MAIN_WINDOW) else {
+        return;
+    };
+    let generation = Arc::new(AtomicU64::new(0));
+    let (tracked, store) = (window.clone(), store.clone());
+    window.on_window_event(move |event| {
+        if !matches!(event, WindowEvent::Moved(_) | WindowEvent::Resized(_)) {
+            return;
+        }
+        // Entering or leaving fullscreen resizes the window
+        if matches!(event, WindowEvent::Resized(_)) {
+            apply_always_on_top(&tracked, &store);
+        }
+        let current = generation.fetch_add(1, Ordering::AcqRel) + 1;
+        let (generation, window, store) = (generation.clone(), tracked.clone(), store.clone());
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(SAVE_DEBOUNCE).await;
+            if generation.load(Ordering::Acquire) == current {
+                save_window_state(&window, &store);
+            }
+        });
+    });
+}
+
+/// The state to save after the window changed to `measured`. While maximized
+/// or fullscreen only the flags change, so the saved bounds stay the ones to
+/// go back to when the window is unmaximized.
+fn updated_state(previous: Option<&WindowState>, measured: WindowState) -> WindowState {
+    match previous {
+        Some(previous) if measured.maximized || measured.fullscreen => WindowState {
+            maximized: measured.maximized,
+            fullscreen: measured.fullscreen,
+            ..previous.clone()
+        },
+        _ => measured,
+    }
+}
+
+/// The window's current geometry in the form it's saved in
+fn measure(window: &WebviewWindow) -> Option<WindowState> {
+    let (Ok(position), Ok(size), Ok(Some(monitor))) = (
+        window.outer_position(),
+        window.inner_size(),
+        window.current_monitor(),
+    ) else {
+        return None;
+    };
+    let area = monitor.work_area();
+    let scale = monitor.scale_factor();
+    let logical = |physical: i64| (physical as f64 / scale).round() as i64;
+    Some(WindowState {
+        x: logical(i64::from(position.x) - i64::from(area.position.x)) as i32,
+        y: logical(i64::from(position.y) - i64::from(area.position.y)) as i32,
+        width: logical(i64::from(size.width)) as u32,
+        height: logical(i64::from(size.height)) as u32,
+        maximized: window.is_maximized().unwrap_or(false),
+        fullscreen: window.is_fullscreen().unwrap_or(false),
+        monitor: Some(MonitorId::of(&monitor)),
+    })
+}
+
+fn save_window_state(window: &WebviewWindow, store: &SettingsStore) {
+    // Minimized windows report a bogus position on some platforms
+    if !window.is_visible().unwrap_or(false) || window.is_minimized().unwrap_or(false) {
+        return;
+    }
+    let Some(measured) = measure(window) else {
+        return;
+    };
+    let previous = store.settings().window_state;
+    let state = updated_state(previous.as_ref(), measured);
+    if previous.as_ref() == Some(&state) {
+        return;
+    }
+    if let Err(e) = store.update(|settings| settings.window_state = Some(state)) {
+        log::warn!("[Window] Failed to save window state: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAPTOP: Area = Area {
+        x: 0,
+        y: 0,
+        width: 1440,
+        height: 900,
+    };
+    const EXTERNAL: Area = Area {
+        x: 1440,
+        y: 0,
+        width: 2560,
+        height: 1440,
+    };
+
+    fn window(x: i32, y: i32, width: u32, height: u32) -> WindowState {
+        WindowState {
+            x,
+            y,
+            width,
+            height,
+            maximized: false,
+            fullscreen: false,
+            monitor: None,
+        }
+    }
+
+    fn display(name: &str, area: Area, scale: f64, primary: bool) -> Display {
+        Display {
+            id: MonitorId {
+                name: Some(name.to_string()),
+                x: area.x,
+                y: area.y,
+            },
+            area,
+            scale,
+            primary,
+        }
+    }
+
+    fn on(monitor: &str, mut state: WindowState) -> WindowState {
+        state.monitor = Some(MonitorId {
+            name: Some(monitor.to_string()),
+            x: 0,
+            y: 0,
+        });
+        state
+    }
+
+    #[test]
+    fn saved_windows_are_clamped_to_a_monitor() {
+        let both = [LAPTOP, EXTERNAL];
+        // Fully on the external monitor: untouched
+        assert_eq!(
+            fit_to_monitors(&window(2000, 100, 1200, 800), &both),
+            Some(window(2000, 100, 1200, 800))
+        );
+        // Hanging off the bottom right of the laptop: pulled back in
+        assert_eq!(
+            fit_to_monitors(&window(1000, 600, 1200, 800), &[LAPTOP]),
+            Some(window(240, 100, 1200, 800))
+        );
+        // Bigger than the monitor: shrunk to fit
+        assert_eq!(
+            fit_to_monitors(&window(-10, -10, 3000, 2000), &[LAPTOP]),
+            Some(window(0, 0, 1440, 900))
+        );
+        // Saved on the external monitor, which is now unplugged
+        assert_eq!(
+            fit_to_monitors(&window(2000, 100, 1200, 800), &[LAPTOP]),
+            None
+        );
+        assert_eq!(fit_to_monitors(&window(0, 0, 800, 600), &[]), None);
+    }
+
+    #[test]
+    fn saved_monitor_is_preferred_in_logical_pixels() {
+        let laptop = display("Built-in Retina Display", LAPTOP, 2.0, true);
+        let external = display("DELL U2720Q", EXTERNAL, 1.0, false);
+        let saved = on("DELL U2720Q", window(100, 50, 600, 400));
+        // Back on the external monitor, even though it isn't the primary one
+        assert_eq!(
+            place_window(&saved, &[laptop.clone(), external]),
+            Some(window(1540, 50, 600, 400))
+        );
+        // External unplugged: the same spot on the primary, scaled to its
+        // density
+        assert_eq!(
+            place_window(&saved, std::slice::from_ref(&laptop)),
+            Some(window(200, 100, 1200, 800))
+        );
+        assert_eq!(place_window(&saved, &[]), None);
+    }
+
+    #[test]
+    fn maximized_windows_keep_their_unmaximized_bounds() {
+        let laptop = display("Built-in Retina Display", LAPTOP, 2.0, true);
+        let normal = on("Built-in Retina Display", window(100, 50, 600, 400));
+        let mut filling = on("Built-in Retina Display", window(0, 0, 720, 450));
+        filling.maximized = true;
+
+        let saved = updated_state(Some(&normal), filling);
+        assert!(saved.maximized);
+        assert_eq!(
+            (saved.x, saved.y, saved.width, saved.height),
+            (100, 50, 600, 400)
+        );
+
+        // Through settings.json and back: flag and bounds both survive, and
+        // the window is placed at the unmaximized bounds before maximizing
+        let json = serde_json::to_value(&saved).unwrap();
+        let loaded: WindowState = serde_json::from_value(json).unwrap();
+        assert_eq!(loaded, saved);
+        let mut expected = window(200, 100, 1200, 800);
+        expected.maximized = true;
+        assert_eq!(place_window(&loaded, &[laptop]), Some(expected));
+
+        // Unmaximizing saves the real bounds again
+        let moved = on("Built-in Retina Display", window(10, 10, 500, 300));
+        assert_eq!(updated_state(Some(&loaded), moved.clone()), moved);
+    }
+}
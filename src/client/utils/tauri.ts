@@ -222,10 +222,232 @@ export async function releaseWakeLock(): Promise<void> {
     }
 }
 
+/**
+ * Open an app route (e.g. `/logs`) in its own window, or focus the window
+ * if one with this label is already open. The label must start with
+ * `secondary-`, e.g. `secondary-logs`.
+ */
+export async function openWindow(label: string, url: string): Promise<void> {
+    if (!isTauri()) return;
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        await invoke('open_window', { label, url });
+    } catch (err) {
+        console.warn(`[window] Failed to open ${label}:`, err);
+    }
+}
+
+/**
+ * Zoom the app and remember it for next launch.
+ * Returns the factor applied (clamped to 0.5–3), or null outside Tauri or on failure.
+ */
+export async function setZoom(factor: number): Promise<number | null> {
+    if (!isTauri()) return null;
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        return await invoke<number>('set_zoom', { factor });
+    } catch (err) {
+        console.warn('[window] Failed to set zoom:', err);
+        return null;
+    }
+}
+
+/**
+ * The current zoom factor (1 outside Tauri).
+ */
+export async function getZoom(): Promise<number> {
+    if (!isTauri()) return 1;
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        return await invoke<number>('get_zoom');
+    } catch (err) {
+        console.warn('[window] Failed to get zoom:', err);
+        return 1;
+    }
+}
+
+/**
+ * Flash the taskbar button or bounce the dock icon while the app is in the
+ * background. `critical` keeps it going until the app is focused.
+ */
+export async function requestUserAttention(critical = false): Promise<void> {
+    if (!isTauri()) return;
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        await invoke('request_user_attention', { critical });
+    } catch (err) {
+        console.warn('[window] Failed to request attention:', err);
+    }
+}
+
+/**
+ * Show or hide the macOS dock icon. No-op on other platforms.
+ */
+export async function setDockVisible(visible: boolean): Promise<void> {
+    if (!isTauri()) return;
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        await invoke('set_dock_visible', { visible });
+    } catch (err) {
+        console.warn('[window] Failed to set dock visibility:', err);
+    }
+}
+
+/**
+ * Hide the window to the tray. On macOS the app also leaves the dock
+ * unless `removeFromDock` is false.
+ */
+export async function hideWindow(options?: { removeFromDock?: boolean }): Promise<void> {
+    if (!isTauri()) return;
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        await invoke('hide_window', { removeFromDock: options?.removeFromDock });
+    } catch (err) {
+        console.warn('[window] Failed to hide:', err);
+    }
+}
+
+/**
+ * Enter or leave fullscreen.
+ * Returns the new state, or null outside Tauri or on failure.
+ */
+export async function toggleFullscreen(): Promise<boolean | null> {
+    if (!isTauri()) return null;
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        return await invoke<boolean>('toggle_fullscreen');
+    } catch (err) {
+        console.warn('[window] Failed to toggle fullscreen:', err);
+        return null;
+    }
+}
+
+/**
+ * Make the window see-through (0.2–1) and remember it for next launch.
+ * Returns the opacity applied, or null outside Tauri or on failure.
+ */
+export async function setWindowOpacity(opacity: number): Promise<number | null> {
+    if (!isTauri()) return null;
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        return await invoke<number>('set_window_opacity', { opacity });
+    } catch (err) {
+        console.warn('[window] Failed to set opacity:', err);
+        return null;
+    }
+}
+
+/**
+ * Pin the window above other windows, or unpin it.
+ * Returns the new state, or null outside Tauri or on failure.
+ */
+export async function toggleAlwaysOnTop(): Promise<boolean | null> {
+    if (!isTauri()) return null;
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        return await invoke<boolean>('toggle_always_on_top');
+    } catch (err) {
+        console.warn('[window] Failed to toggle always on top:', err);
+        return null;
+    }
+}
+
+/**
+ * Start the app when the user logs in, or stop doing so.
+ */
+export async function setLaunchAtLogin(enabled: boolean): Promise<void> {
+    if (!isTauri()) return;
+    const { invoke } = await import('@tauri-apps/api/core');
+    await invoke('set_launch_at_login', { enabled });
+}
+
+/**
+ * Change the key combo that shows and hides the app, e.g. `Alt+Space`.
+ * Throws if the combo is invalid or taken, leaving the current one in place.
+ */
+export async function setShowHideShortcut(shortcut: string): Promise<void> {
+    if (!isTauri()) return;
+    const { invoke } = await import('@tauri-apps/api/core');
+    await invoke('set_show_hide_shortcut', { shortcut });
+}
+
+/**
+ * Whether the app is registered to start at login (false outside Tauri).
+ */
+export async function getLaunchAtLogin(): Promise<boolean> {
+    if (!isTauri()) return false;
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        return await invoke<boolean>('get_launch_at_login');
+    } catch (err) {
+        console.warn('[autostart] Failed to read launch at login:', err);
+        return false;
+    }
+}
+
+/**
+ * Show a native notification. `onClick` is handed back through
+ * `notification-clicked` when the user clicks it. Returns false if
+ * notifications are turned off, not permitted or unavailable.
+ */
+export async function notify(title: string, body: string, onClick?: unknown): Promise<boolean> {
+    if (!isTauri()) return false;
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        return await invoke<boolean>('notify', { title, body, onClick });
+    } catch (err) {
+        console.warn('[notify] Failed to show notification:', err);
+        return false;
+    }
+}
+
+/**
+ * Listen for notification clicks, receiving the `onClick` payload passed to `notify`.
+ */
+export async function onNotificationClicked(callback: (payload: unknown) => void): Promise<() => void> {
+    if (!isTauri()) return () => {};
+    const { listen } = await import('@tauri-apps/api/event');
+    return listen<unknown>('notification-clicked', (event) => callback(event.payload));
+}
+
+/**
+ * Ask where to save with the native save dialog and write `contents` there.
+ * Returns the saved path, or null if the dialog was cancelled or outside Tauri.
+ * Throws with a readable message if the file couldn't be written.
+ */
+export async function saveFileDialog(defaultName: string, contents: string | Uint8Array): Promise<string | null> {
+    if (!isTauri()) return null;
+    const { invoke } = await import('@tauri-apps/api/core');
+    const bytes = typeof contents === 'string' ? new TextEncoder().encode(contents) : contents;
+    return invoke<string | null>('save_file_dialog', { defaultName, contents: Array.from(bytes) });
+}
+
+/**
+ * Export settings to a file picked in the save dialog.
+ * Returns the saved path, or null if the dialog was cancelled or outside Tauri.
+ */
+export async function exportSettingsWithDialog(): Promise<string | null> {
+    if (!isTauri()) return null;
+    const { invoke } = await import('@tauri-apps/api/core');
+    return invoke<string | null>('export_settings_with_dialog');
+}
+
+/**
+ * Change how much the app logs (off, error, warn, info, debug or trace),
+ * effective immediately and kept for next launch. Throws on an unknown level.
+ */
+export async function setLogLevel(level: string): Promise<void> {
+    if (!isTauri()) return;
+    const { invoke } = await import('@tauri-apps/api/core');
+    await invoke('set_log_level', { level });
+}
+
 /**
  * Listen for deep link events from Tauri.
  * Deep links are custom URL schemes (e.g., pipali://chat/conversationId) that
- * can be used to navigate the app to specific locations.
+ * can be used to navigate the app to specific locations. Links received before
+ * the listener was set up, like the one the app was launched with, are
+ * delivered right away.
  *
  * @param callback - Function to call when a deep link is received, with the URL string
  * @returns Cleanup function to unsubscribe from the event
@@ -237,9 +459,12 @@ export async function listenForDeepLinks(callback: (url: string) => void): Promi
 
     try {
         const { listen } = await import('@tauri-apps/api/event');
+        const { invoke } = await import('@tauri-apps/api/core');
         const unlisten = await listen<string>('deep-link', (event) => {
             callback(event.payload);
         });
+        const pending = await invoke<string[]>('take_pending_deep_links');
+        pending.forEach(callback);
         return unlisten;
     } catch (err) {
         console.warn('[tauri] Failed to setup deep link listener:', err);
@@ -255,6 +480,55 @@ export interface AttachedFileInfo {
     filePath: string;
     fileName: string;
     sizeBytes: number;
+    /** Unix epoch millis; null where the filesystem doesn't record them */
+    modifiedMs: number | null;
+    createdMs: number | null;
+    mimeType: string;
+    /** Hex SHA-256 of the contents, only when requested with `hash` */
+    sha256: string | null;
+    /** Position within the dropped folder, for files found by expanding one */
+    relativePath: string | null;
+    /** Pixel dimensions, for images whose header could be read */
+    width: number | null;
+    height: number | null;
+    /** Whether the contents look like text rather than binary data */
+    isText: boolean;
+    /** Character encoding of text files, e.g. `UTF-8` or `UTF-16LE` */
+    detectedEncoding: string | null;
+    /** The start of the file, for text files */
+    preview: string | null;
+    /** Canonical path of the file, when symlinks are followed */
+    resolvedPath: string | null;
+    /** Set when the file can't be attached, with the reason why */
+    rejected: boolean;
+    reason: string | null;
+    /** Path of an earlier file in the drop with the same contents */
+    duplicateOf: string | null;
+}
+
+/** Options for `getDroppedFileMetadata`; anything left out uses the native default. */
+export interface DropOptions {
+    hash?: boolean;
+    /** Hash files and point repeats at the first file with the same contents */
+    dedupe?: boolean;
+    recurse?: boolean;
+    maxDepth?: number;
+    /** Cap on files read for the whole drop; files past it are only counted */
+    maxFiles?: number;
+    previewBytes?: number;
+    followSymlinks?: boolean;
+    maxSizeBytes?: number;
+    /** Extensions to accept, case-insensitive; `''` accepts files with no extension */
+    allowedExtensions?: string[];
+}
+
+export interface DroppedFiles {
+    files: AttachedFileInfo[];
+    skipped: { path: string; reason: string }[];
+    /** Set when `maxFiles` was hit and later files were left out */
+    truncated: boolean;
+    /** Every file found in the drop, including those past `maxFiles` */
+    totalFiles: number;
 }
 
 /** Listen for native file-drag-enter events from Tauri. */
@@ -323,14 +597,120 @@ export async function pickPath(options?: { directory?: boolean }): Promise<strin
     }
 }
 
-/** Get metadata for dropped files via Tauri command. */
-export async function getDroppedFileMetadata(paths: string[]): Promise<AttachedFileInfo[]> {
+/** Read dropped files via Tauri command, including what was skipped and whether the batch was truncated. */
+export async function getDroppedFiles(paths: string[], options?: DropOptions): Promise<DroppedFiles> {
+    const empty: DroppedFiles = { files: [], skipped: [], truncated: false, totalFiles: 0 };
+    if (!isTauri()) return empty;
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        return await invoke<DroppedFiles>('get_dropped_file_metadata', { paths, options });
+    } catch (err) {
+        console.error('[getDroppedFiles] Failed:', err);
+        return empty;
+    }
+}
+
+/** Get metadata for dropped files via Tauri command. Skipped paths are logged. */
+export async function getDroppedFileMetadata(paths: string[], options?: DropOptions): Promise<AttachedFileInfo[]> {
+    const { files, skipped } = await getDroppedFiles(paths, options);
+    for (const { path, reason } of skipped) {
+        console.warn(`[getDroppedFileMetadata] Skipped ${path}: ${reason}`);
+    }
+    return files;
+}
+
+export interface DropSummary {
+    totalBytes: number;
+    fileCount: number;
+    skippedCount: number;
+    /** Whether `totalBytes` is over the budget, when one was given */
+    overBudget: boolean | null;
+}
+
+/** Total size and count of dropped files, without reading them. Folders count their contents with `recurse`. */
+export async function getDroppedFilesSummary(paths: string[], options?: DropOptions, maxTotalBytes?: number): Promise<DropSummary | null> {
+    if (!isTauri()) return null;
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        return await invoke<DropSummary>('get_dropped_files_summary', { paths, options, maxTotalBytes });
+    } catch (err) {
+        console.error('[getDroppedFilesSummary] Failed:', err);
+        return null;
+    }
+}
+
+export interface ImportedFile {
+    originalPath: string;
+    /** Where the copy now lives, if the import worked */
+    storedPath: string | null;
+    /** Name the copy was stored under, numbered when another file in the batch had it */
+    fileName: string | null;
+    error: string | null;
+    /** Original path of an earlier file in the batch whose copy was reused */
+    duplicateOf: string | null;
+}
+
+/** Copy dropped files into app storage so they outlive the originals. */
+export async function importDroppedFiles(paths: string[], options?: { dedupe?: boolean }): Promise<ImportedFile[]> {
+    if (!isTauri()) return [];
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        return await invoke<ImportedFile[]>('import_dropped_files', { paths, dedupe: options?.dedupe ?? false });
+    } catch (err) {
+        console.error('[importDroppedFiles] Failed:', err);
+        return [];
+    }
+}
+
+/**
+ * Open an attached file or folder in its default app.
+ * Throws with a readable message if the path can't be opened.
+ */
+export async function openPath(path: string): Promise<void> {
+    if (!isTauri()) return;
+    const { invoke } = await import('@tauri-apps/api/core');
+    await invoke('open_path', { path });
+}
+
+/**
+ * Show a file selected in its folder in Finder, Explorer or the file manager.
+ * Throws with a readable message if the path doesn't exist.
+ */
+export async function revealInFileManager(path: string): Promise<void> {
+    if (!isTauri()) return;
+    const { invoke } = await import('@tauri-apps/api/core');
+    await invoke('reveal_in_file_manager', { path });
+}
+
+/**
+ * Save the image on the clipboard (e.g. a screenshot) as a PNG attachment.
+ * Returns null when the clipboard holds no image.
+ */
+export async function attachClipboardImage(): Promise<AttachedFileInfo | null> {
+    if (!isTauri()) return null;
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        return await invoke<AttachedFileInfo | null>('attach_clipboard_image');
+    } catch (err) {
+        console.error('[attachClipboardImage] Failed:', err);
+        return null;
+    }
+}
+
+export interface ThumbnailResult {
+    sourcePath: string;
+    /** `data:` URL of the thumbnail, or null if the file isn't a readable image */
+    dataUrl: string | null;
+}
+
+/** Generate thumbnails no larger than `maxEdge` pixels for dropped images. */
+export async function generateThumbnails(paths: string[], maxEdge: number): Promise<ThumbnailResult[]> {
     if (!isTauri()) return [];
     try {
         const { invoke } = await import('@tauri-apps/api/core');
-        return await invoke<AttachedFileInfo[]>('get_dropped_file_metadata', { paths });
+        return await invoke<ThumbnailResult[]>('generate_thumbnails', { paths, maxEdge });
     } catch (err) {
-        console.error('[getDroppedFileMetadata] Failed:', err);
+        console.error('[generateThumbnails] Failed:', err);
         return [];
     }
 }
@@ -9,6 +9,7 @@ import { db, getDefaultChatModel } from '../db';
 import { Conversation } from '../db/schema';
 import { eq, desc, isNull, and, sql } from 'drizzle-orm';
 import { AiModelApi, ChatModel, User, UserChatModel } from '../db/schema';
+import packageJson from '../../../package.json';
 import openapi from './openapi';
 import automations from './automations';
 import mcp from './mcp';
@@ -55,6 +56,25 @@ api.use('*', cors({
 // Health check endpoint for Tauri sidecar readiness detection
 api.get('/health', (c) => c.json({ status: 'ok' }));
 
+// Graceful stop for the desktop app on Windows, which can't send SIGTERM to
+// a windowless process. Only honoured with the token the app launched us with.
+api.post('/shutdown', (c) => {
+    const token = process.env.PIPALI_SHUTDOWN_TOKEN;
+    if (!token || c.req.header('X-Pipali-Shutdown-Token') !== token) {
+        return c.json({ error: 'Forbidden' }, 403);
+    }
+    log.info('Shutdown requested by the desktop app');
+    // Let the response go out before the server stops
+    setTimeout(() => process.emit('SIGTERM'), 0);
+    return c.json({ status: 'shutting down' }, 202);
+});
+
+// Build info, so the desktop app can show which server is running
+api.get('/version', (c) => c.json({
+    version: packageJson.version,
+    commit: process.env.PIPALI_GIT_COMMIT || null,
+}));
+
 const schema = z.object({
     message: z.string(),
     conversationId: z.uuid().optional(),